@@ -0,0 +1,333 @@
+//! The demo scenes: textured spinning cubes on a floor with a few point lights, a directional
+//! light and a flashlight, a single sphere, and a row of spheres and cubes of increasing
+//! shininess. Switch between them with F4. Run with `cargo run --example demo` from the
+//! repository root.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use winit::event_loop::EventLoop;
+
+use opengl_rust::app::{App, AppConfig};
+use opengl_rust::renderer::material::{Material, MaterialProperty};
+use opengl_rust::renderer::mesh::{Mesh, Vertex};
+use opengl_rust::renderer::shader::ShaderProgram;
+use opengl_rust::renderer::shader_variants::ShaderSource;
+use opengl_rust::renderer::texture::{Texture2D, TextureConfig};
+use opengl_rust::scene::{Animator, Light, Scene};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = AppConfig {
+        shader_cache_dir: Some(PathBuf::from("./shader_cache")),
+        key_bindings_file: Some(PathBuf::from("./key_bindings.txt")),
+        ..Default::default()
+    };
+    let mut app = App::new(config, build_cube_field)
+        .with_scene("Sphere", build_single_sphere)
+        .with_scene("Material test", build_material_test);
+    let event_loop = EventLoop::new()?;
+    event_loop.run_app(&mut app)?;
+
+    app.get_exit_state()
+}
+
+// Meshes and materials shared by all the scenes, registered under the names they're spawned with
+fn register_resources(scene: &mut Scene) -> Result<(), String> {
+    let cube_vertices: [Vertex; 36] = [
+        Vertex::new([-0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 0.0]),
+        Vertex::new([0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 1.0]),
+        Vertex::new([0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 1.0]),
+        Vertex::new([-0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 1.0]),
+        Vertex::new([-0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 0.0]),
+        Vertex::new([-0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 0.0]),
+        Vertex::new([0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 1.0]),
+        Vertex::new([-0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 0.0]),
+        Vertex::new([-0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], [1.0, 0.0]),
+        Vertex::new([-0.5, 0.5, -0.5], [-1.0, 0.0, 0.0], [1.0, 1.0]),
+        Vertex::new([-0.5, -0.5, -0.5], [-1.0, 0.0, 0.0], [0.0, 1.0]),
+        Vertex::new([-0.5, -0.5, -0.5], [-1.0, 0.0, 0.0], [0.0, 1.0]),
+        Vertex::new([-0.5, -0.5, 0.5], [-1.0, 0.0, 0.0], [0.0, 0.0]),
+        Vertex::new([-0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.5], [1.0, 0.0, 0.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, -0.5], [1.0, 0.0, 0.0], [1.0, 1.0]),
+        Vertex::new([0.5, -0.5, -0.5], [1.0, 0.0, 0.0], [0.0, 1.0]),
+        Vertex::new([0.5, -0.5, -0.5], [1.0, 0.0, 0.0], [0.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.5], [1.0, 0.0, 0.0], [0.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.5], [1.0, 0.0, 0.0], [1.0, 0.0]),
+        Vertex::new([-0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [0.0, 1.0]),
+        Vertex::new([0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [1.0, 1.0]),
+        Vertex::new([0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [1.0, 0.0]),
+        Vertex::new([0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [1.0, 0.0]),
+        Vertex::new([-0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [0.0, 0.0]),
+        Vertex::new([-0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [0.0, 1.0]),
+        Vertex::new([-0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [0.0, 1.0]),
+        Vertex::new([0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [1.0, 1.0]),
+        Vertex::new([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [1.0, 0.0]),
+        Vertex::new([-0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [0.0, 0.0]),
+        Vertex::new([-0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [0.0, 1.0]),
+    ];
+
+    // ==== Shaders ====
+    // Lit objects, compiled with a `HAS_<TEXTURE>` define per texture of the material
+    let objects_shader =
+        ShaderSource::new("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs");
+
+    // Unlit shader, used for the light sources
+    let unlit_shader = Rc::new(ShaderProgram::from_files(
+        "./shaders/unlit.vs",
+        "./shaders/unlit.fs",
+    )?);
+
+    // ==== Textures ====
+    // A missing file shows up as a checkerboard instead of stopping the demo
+    let container_texture_diffuse = Rc::new(Texture2D::new_from_file_or_default(
+        "./textures/container2.png",
+        &TextureConfig::srgb(),
+    ));
+    let container_texture_specular = Rc::new(Texture2D::new_from_file_or_default(
+        "./textures/container2_specular.png",
+        &TextureConfig::grayscale(),
+    ));
+
+    // ==== Meshes ====
+    let mut cube_mesh = Mesh::new();
+    cube_mesh.init(&cube_vertices, None);
+    scene.register_mesh("cube", Rc::new(cube_mesh));
+    scene.register_mesh("sphere", Rc::new(sphere_mesh(32, 16)));
+
+    // ==== Materials ====
+    let phong_material = Rc::new(RefCell::new(Material::with_variants(
+        "phong_textured",
+        &scene.shader_variants(),
+        objects_shader,
+        [
+            (
+                "material.diffuse".to_string(),
+                MaterialProperty::Texture(Rc::clone(&container_texture_diffuse)),
+            ),
+            (
+                "material.specular".to_string(),
+                MaterialProperty::Texture(Rc::clone(&container_texture_specular)),
+            ),
+            (
+                "material.shininess".to_string(),
+                MaterialProperty::Integer(32),
+            ),
+            (
+                "material.uv_tiling".to_string(),
+                MaterialProperty::Vec2([1.0, 1.0]),
+            ),
+            (
+                "material.uv_offset".to_string(),
+                MaterialProperty::Vec2([0.0, 0.0]),
+            ),
+        ]
+        .into(),
+    )?));
+
+    let light_material = Rc::new(RefCell::new(Material::unlit(
+        Rc::clone(&unlit_shader),
+        [1.0, 1.0, 1.0],
+    )));
+    light_material.borrow_mut().set_name("light_source");
+
+    #[cfg(debug_assertions)]
+    for material in [&phong_material, &light_material] {
+        let material = material.borrow();
+        if let Err(names) = material.validate() {
+            println!(
+                "Material '{}' has properties without a matching uniform: {}",
+                material.name(),
+                names.join(", ")
+            );
+        }
+    }
+    scene.register_material("phong", phong_material);
+    scene.register_material("light_source", light_material);
+
+    // Every scene starts with a dim white ambient light, the GUI can change it
+    scene.ambient_light.intensity = 0.1;
+
+    Ok(())
+}
+
+fn build_cube_field(scene: &mut Scene) -> Result<(), String> {
+    register_resources(scene)?;
+
+    let cube_positions = [
+        glam::Vec3::new(0.0, 0.0, 0.0),
+        glam::Vec3::new(2.0, 5.0, -15.0),
+        glam::Vec3::new(-1.5, -2.2, -2.5),
+        glam::Vec3::new(-3.8, -2.0, -12.3),
+        glam::Vec3::new(2.4, -0.4, -3.5),
+        glam::Vec3::new(-1.7, 3.0, -7.5),
+        glam::Vec3::new(1.3, -2.0, -2.5),
+        glam::Vec3::new(1.5, 2.0, -2.5),
+        glam::Vec3::new(1.5, 0.2, -1.5),
+        glam::Vec3::new(-1.3, 1.0, -1.5),
+    ];
+
+    for (i, position) in cube_positions.into_iter().enumerate() {
+        let cube = scene.spawn("cube", "phong")?;
+        let mut cube = cube.borrow_mut();
+        cube.transform.position = position;
+        // Each cube spins a bit faster than the previous one
+        let speed = (20.0 * i as f32).to_radians();
+        cube.animator = Some(Animator::spin(glam::Vec3::new(1.0, 0.3, 0.5), speed));
+    }
+
+    // Floor
+    {
+        let floor = scene.spawn("cube", "phong")?;
+        let mut floor = floor.borrow_mut();
+        floor.transform.position = glam::vec3(0.0, -3.0, 0.0);
+        floor.transform.scale = glam::Vec3::new(50.0, 0.1, 50.0);
+        // One container tile every 2 units instead of a single stretched texture
+        floor.material_overrides.set_vec2("material.uv_tiling", [25.0, 25.0]);
+    }
+
+    // Light sources
+    let light_positions = [
+        glam::Vec3::new(0.7, 0.2, 2.0),
+        glam::Vec3::new(2.3, 10.3, -4.0),
+        glam::Vec3::new(-4.0, 2.0, -12.0),
+        glam::Vec3::new(0.0, 0.0, -3.0),
+    ];
+
+    for position in light_positions {
+        // Light source object
+        let emitter = scene.spawn("cube", "light_source")?;
+        {
+            let mut emitter = emitter.borrow_mut();
+            emitter.transform.position = position;
+            emitter.transform.scale = glam::Vec3::splat(0.2);
+        }
+
+        // Actual Light
+        let light = Rc::new(RefCell::new(Light::new_point_light()));
+        {
+            let mut light = light.borrow_mut();
+            light.position = position;
+        }
+        scene.add_light(Rc::clone(&light));
+        scene.add_light_emitter(emitter, light);
+    }
+
+    // Directional light
+    let light = Rc::new(RefCell::new(Light::new_directional_light()));
+    light.borrow_mut().intensity = 0.4;
+    light
+        .borrow_mut()
+        .as_directional_light_mut()
+        .unwrap()
+        .direction = glam::Vec3::new(-0.2, -1.0, -0.3);
+    scene.add_light(light);
+
+    // Flashlight
+    let light = Rc::new(RefCell::new(Light::new_spot_light()));
+    light
+        .borrow_mut()
+        .as_spot_light_mut()
+        .unwrap()
+        .attached_to_camera = true;
+    scene.add_light(light);
+
+    Ok(())
+}
+
+fn build_single_sphere(scene: &mut Scene) -> Result<(), String> {
+    register_resources(scene)?;
+
+    let sphere = scene.spawn("sphere", "phong")?;
+    sphere.borrow_mut().transform.position = glam::Vec3::new(0.0, 0.0, -1.5);
+    sphere.borrow_mut().animator = Some(Animator::spin(glam::Vec3::Y, 20f32.to_radians()));
+
+    let light = Rc::new(RefCell::new(Light::new_point_light()));
+    light.borrow_mut().position = glam::Vec3::new(1.5, 1.0, 2.0);
+    scene.add_light(light);
+
+    let light = Rc::new(RefCell::new(Light::new_directional_light()));
+    light.borrow_mut().intensity = 0.2;
+    light
+        .borrow_mut()
+        .as_directional_light_mut()
+        .unwrap()
+        .direction = glam::Vec3::new(-0.2, -1.0, -0.3);
+    scene.add_light(light);
+
+    Ok(())
+}
+
+// Spheres on top and cubes below, shininess doubling from left to right
+fn build_material_test(scene: &mut Scene) -> Result<(), String> {
+    register_resources(scene)?;
+
+    let shininess = [2, 8, 32, 128, 512];
+    for (row, mesh) in ["sphere", "cube"].into_iter().enumerate() {
+        for (column, shininess) in shininess.into_iter().enumerate() {
+            let object = scene.spawn(mesh, "phong")?;
+            let mut object = object.borrow_mut();
+            object.transform.position = glam::Vec3::new(
+                (column as f32 - 2.0) * 1.25,
+                0.75 - row as f32 * 1.5,
+                -5.0,
+            );
+            object
+                .material_overrides
+                .set_integer("material.shininess", shininess);
+        }
+    }
+
+    let light = Rc::new(RefCell::new(Light::new_point_light()));
+    light.borrow_mut().position = glam::Vec3::new(0.0, 2.0, -2.0);
+    scene.add_light(light);
+
+    let light = Rc::new(RefCell::new(Light::new_directional_light()));
+    light.borrow_mut().intensity = 0.3;
+    light
+        .borrow_mut()
+        .as_directional_light_mut()
+        .unwrap()
+        .direction = glam::Vec3::new(-0.2, -0.5, -1.0);
+    scene.add_light(light);
+
+    Ok(())
+}
+
+// UV sphere of radius 0.5, `segments` around the Y axis and `rings` from pole to pole
+fn sphere_mesh(segments: u32, rings: u32) -> Mesh {
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+            let normal = [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()];
+            let position = normal.map(|n| n * 0.5);
+            let uv = [
+                segment as f32 / segments as f32,
+                1.0 - ring as f32 / rings as f32,
+            ];
+            vertices.push(Vertex::new(position, normal, uv));
+        }
+    }
+
+    let mut indices = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let top = ring * (segments + 1) + segment;
+            let bottom = top + segments + 1;
+            indices.extend_from_slice(&[top, top + 1, bottom, top + 1, bottom + 1, bottom]);
+        }
+    }
+
+    let mut mesh = Mesh::new();
+    mesh.init(&vertices, Some(&indices));
+    mesh
+}
@@ -0,0 +1,9 @@
+// Light attenuation over distance, shared by the lit shaders. Matches `Falloff` in light.rs.
+#define FALLOFF_COEFFICIENTS 0
+#define FALLOFF_INVERSE_SQUARE 1
+
+float Attenuation(int falloff, float constant, float linear, float quadratic, float distance) {
+    if(falloff == FALLOFF_INVERSE_SQUARE)
+        return 1.0 / max(distance * distance, 0.0001);
+    return 1.0 / (constant + linear * distance + quadratic * (distance * distance));
+}
@@ -1,9 +1,22 @@
 #version 450 core
 
+// Shader variants define HAS_<PROPERTY> for each texture of the material. Compiled without
+// variant defines, both maps are expected.
+#ifndef VARIANT_DEFINES
+#define HAS_MATERIAL_DIFFUSE 1
+#define HAS_MATERIAL_SPECULAR 1
+#endif
+
 struct Material {
+#ifdef HAS_MATERIAL_DIFFUSE
     sampler2D diffuse;
+#endif
+#ifdef HAS_MATERIAL_SPECULAR
     sampler2D specular;
+#endif
     int shininess;
+    vec2 uv_tiling; // UVs are scaled then offset before sampling
+    vec2 uv_offset;
 };
 
 struct AmbientLight {
@@ -13,22 +26,27 @@ struct AmbientLight {
 
 struct DirectionalLight {
     vec4 color;
+    vec4 specular; // Tint of the highlights, the color unless set separately
     vec4 direction;
     float intensity;
 };
 
 struct PointLight {
     vec4 color;
+    vec4 specular;
     vec4 position;
+    vec4 emitDirection; // Side lit by area light samples (w = 1), w = 0 lights every side
 
     float constant;
     float linear;
     float quadratic;
     float intensity;
+    int falloff;
 };
 
 struct SpotLight {
     vec4 color;
+    vec4 specular;
     vec4 position;
     vec4 direction;
 
@@ -40,6 +58,7 @@ struct SpotLight {
     float quadratic;
 
     float intensity;
+    int falloff;
 };
 
 in vec2 TexCoord;
@@ -54,22 +73,27 @@ layout (std140, binding = 0) uniform Camera {
     vec4 position;
 } camera;
 
-#define MAX_POINT_LIGHTS 10
 #define MAX_SPOT_LIGHTS 5
 #define MAX_DIRECTIONAL_LIGHTS 5
 layout (std140, binding = 1) uniform LightData {
     AmbientLight ambient;
     DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
-    PointLight pointLights[MAX_POINT_LIGHTS];
     SpotLight spotLights[MAX_SPOT_LIGHTS];
     int nrPointLights;
     int nrSpotLights;
     int nrDirectionalLights;
 } lights;
 
-uniform bool isFloor;
-uniform vec3 floorColor;
+// Point lights are sized at runtime, lights.nrPointLights holds the count
+layout (std430, binding = 0) readonly buffer PointLightData {
+    PointLight pointLights[];
+};
+
+#include "attenuation.glsl"
+
 uniform Material material;
+// Linear tint multiplied with the diffuse map, e.g. a glTF base color factor
+uniform vec3 diffuseFactor = vec3(1.0);
 
 vec3 CalculateDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir, vec3 diffuse_color, vec3 specular_color) {
     vec3 lightDir = normalize(-light.direction.xyz);
@@ -78,7 +102,7 @@ vec3 CalculateDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir
 
     vec3 reflectDir = reflect(-lightDir, normal);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = spec * light.color.rgb * specular_color;
+    vec3 specular = spec * light.specular.rgb * specular_color;
 
     return light.intensity * (diffuse + specular);
 }
@@ -91,12 +115,14 @@ vec3 CalculatePointLight(PointLight light, vec3 normal, vec3 viewDir, vec3 diffu
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
 
     float distance = length(light.position.xyz - FragPos);
-    float attenuation = 1.0 / (light.constant + light.linear * distance + light.quadratic * (distance * distance));
+    float attenuation = Attenuation(light.falloff, light.constant, light.linear, light.quadratic, distance);
+    // Area light samples fade towards the edge of the quad and don't light what's behind it
+    float facing = mix(1.0, max(dot(light.emitDirection.xyz, -lightDir), 0.0), light.emitDirection.w);
 
     vec3 diffuse = diff * light.color.rgb * diffuse_color;
-    vec3 specular = light.color.rgb * specular_color * spec;
+    vec3 specular = light.specular.rgb * specular_color * spec;
 
-    return light.intensity * attenuation * (diffuse + specular);
+    return light.intensity * attenuation * facing * (diffuse + specular);
 }
 
 vec3 CalculateSpotlight(SpotLight light, vec3 normal, vec3 viewDir, vec3 diffuse_color, vec3 specular_color) {
@@ -104,47 +130,48 @@ vec3 CalculateSpotlight(SpotLight light, vec3 normal, vec3 viewDir, vec3 diffuse
     float diff = max(dot(normal, lightDir), 0.0);
 
     float theta = dot(lightDir, normalize(-light.direction.xyz));
-    float epsilon = light.cutOff_cos - light.outerCutOff_cos;
+    // Equal cone angles give a hard edge instead of a division by zero
+    float epsilon = max(light.cutOff_cos - light.outerCutOff_cos, 1e-4);
     float intensity = clamp((theta - light.outerCutOff_cos) / epsilon, 0.0, 1.0);
 
     vec3 reflectDir = reflect(-lightDir, normal);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
 
     float distance = length(light.position.xyz - FragPos);
-    float attenuation = 1.0 / (light.constant + light.linear * distance + light.quadratic * (distance * distance));
+    float attenuation = Attenuation(light.falloff, light.constant, light.linear, light.quadratic, distance);
 
     vec3 diffuse =  light.color.rgb * diffuse_color * diff;
-    vec3 specular = light.color.rgb * specular_color * spec;
+    vec3 specular = light.specular.rgb * specular_color * spec;
 
     return (diffuse + specular) * intensity * attenuation * light.intensity;
 }
 
 void main()
 {
-    vec3 diffuse_color;
-    if(!isFloor)
-        diffuse_color = texture(material.diffuse, TexCoord).rgb;
-    else
-        diffuse_color = floorColor;
-
-    vec3 specular_color;
-    if(!isFloor)
-        specular_color = texture(material.specular, TexCoord).rgb;
-    else
-        specular_color = floorColor;
+    vec2 uv = TexCoord * material.uv_tiling + material.uv_offset;
+
+    // Without a diffuse map the factor is the color, without a specular map highlights are half strength
+    vec3 diffuse_color = diffuseFactor;
+#ifdef HAS_MATERIAL_DIFFUSE
+    diffuse_color *= texture(material.diffuse, uv).rgb;
+#endif
+    vec3 specular_color = vec3(0.5);
+#ifdef HAS_MATERIAL_SPECULAR
+    specular_color = texture(material.specular, uv).rgb;
+#endif
 
     vec3 viewPos = camera.position.xyz;
     vec3 normal = normalize(Normal);
     vec3 viewDir = normalize(viewPos - FragPos);
 
-    vec3 light;
+    vec3 light = vec3(0.0);
     // Directional lights
     for(int i = 0; i < lights.nrDirectionalLights; i++)
         light += CalculateDirectionalLight(lights.directionalLights[i], normal, viewDir, diffuse_color, specular_color);
 
     // Point lights
     for(int i = 0; i < lights.nrPointLights; i++)
-        light += CalculatePointLight(lights.pointLights[i], normal, viewDir, diffuse_color, specular_color);
+        light += CalculatePointLight(pointLights[i], normal, viewDir, diffuse_color, specular_color);
 
     // Spot lights
     for(int i = 0; i < lights.nrSpotLights; i++)
@@ -7,6 +7,9 @@ out vec2 TexCoord;
 out vec3 Normal;
 out vec3 FragPos;  // position of the fragment in world space, for lighting calculations
 
+// Matches the depth prepass shader exactly
+invariant gl_Position;
+
 layout (std140, binding = 0) uniform Camera {
     mat4 view;
     mat4 projection;
@@ -14,11 +17,12 @@ layout (std140, binding = 0) uniform Camera {
 } camera;
 
 uniform mat4 model;
+uniform mat3 normal_matrix;
 
 void main()
 {
     TexCoord = aTexCoord;
-    Normal = transpose(inverse(mat3(model))) * aNormal;
+    Normal = normal_matrix * aNormal;
     FragPos = vec3(model * vec4(aPos, 1.0));
     gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
 }
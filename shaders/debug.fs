@@ -0,0 +1,27 @@
+#version 450 core
+
+in vec2 TexCoord;
+in vec3 Normal;
+
+out vec4 FragColor;
+
+uniform int mode; // 1 = normals, 2 = depth, 3 = UVs, 4 = overdraw
+uniform float near;
+uniform float far;
+
+void main()
+{
+    if (mode == 1) {
+        // World space normal mapped from [-1, 1] to [0, 1]
+        FragColor = vec4(normalize(Normal) * 0.5 + 0.5, 1.0);
+    } else if (mode == 2) {
+        float ndc = gl_FragCoord.z * 2.0 - 1.0;
+        float linear = 2.0 * near * far / (far + near - ndc * (far - near));
+        FragColor = vec4(vec3(linear / far), 1.0);
+    } else if (mode == 3) {
+        FragColor = vec4(fract(TexCoord), 0.0, 1.0);
+    } else {
+        // Added up with blending, each layer moves the pixel from dark red towards white
+        FragColor = vec4(0.15, 0.06, 0.02, 1.0);
+    }
+}
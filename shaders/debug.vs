@@ -0,0 +1,26 @@
+#version 450 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoord;
+
+out vec2 TexCoord;
+out vec3 Normal;
+
+// Matches the depth prepass shader exactly
+invariant gl_Position;
+
+layout (std140, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    vec4 position;
+} camera;
+
+uniform mat4 model;
+uniform mat3 normal_matrix;
+
+void main()
+{
+    TexCoord = aTexCoord;
+    Normal = normal_matrix * aNormal;
+    gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
+}
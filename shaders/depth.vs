@@ -9,6 +9,9 @@ layout (std140, binding = 0) uniform Camera {
 
 uniform mat4 model;
 
+// Same expression as the other scene shaders, so the color pass can test depth with GL_EQUAL
+invariant gl_Position;
+
 void main()
 {
     gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
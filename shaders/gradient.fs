@@ -0,0 +1,13 @@
+#version 450 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+uniform vec3 topColor;
+uniform vec3 bottomColor;
+
+void main()
+{
+    FragColor = vec4(mix(bottomColor, topColor, TexCoord.y), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 450 core
+out uint fragId;
+
+// Id of the object being drawn, 0 is left for the background
+uniform uint id;
+
+void main()
+{
+    fragId = id;
+}
@@ -0,0 +1,47 @@
+#version 450 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+uniform sampler2D hdrBuffer;
+uniform float exposure;
+uniform bool bloom;
+uniform sampler2D bloomBuffer;
+uniform float bloomIntensity;
+uniform int toneMapping; // 0 = Reinhard, 1 = ACES
+// Part of the buffers drawn to the viewport, in texture coordinates
+uniform vec2 uvOffset;
+uniform vec2 uvScale;
+
+vec3 Reinhard(vec3 color) {
+    return color / (color + vec3(1.0));
+}
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 Aces(vec3 color) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main()
+{
+    vec2 uv = uvOffset + TexCoord * uvScale;
+    vec3 hdrColor = texture(hdrBuffer, uv).rgb;
+    if(bloom)
+        hdrColor += texture(bloomBuffer, uv).rgb * bloomIntensity;
+    hdrColor *= exposure;
+
+    vec3 mapped;
+    if(toneMapping == 1)
+        mapped = Aces(hdrColor);
+    else
+        mapped = Reinhard(hdrColor);
+
+    // Gamma correction is done by the sRGB default framebuffer
+    FragColor = vec4(mapped, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 450 core
+
+in vec2 TexCoord;
+in vec4 VertexColor; // White unless the mesh has vertex colors
+
+out vec4 FragColor;
+
+uniform vec3 color;
+uniform bool useTexture;
+uniform sampler2D colorTexture;
+
+void main()
+{
+    vec4 result = vec4(color, 1.0) * VertexColor;
+    if(useTexture)
+        result *= texture(colorTexture, TexCoord);
+    FragColor = result;
+}
@@ -0,0 +1,25 @@
+#version 450 core
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in vec4 aColor;
+
+out vec2 TexCoord;
+out vec4 VertexColor;
+
+layout (std140, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    vec4 position;
+} camera;
+
+uniform mat4 model;
+
+// Matches the depth prepass shader exactly
+invariant gl_Position;
+
+void main()
+{
+    TexCoord = aTexCoord;
+    VertexColor = aColor;
+    gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
+}
@@ -1,24 +1,48 @@
 use std::error::Error;
 use std::num::NonZero;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use glutin::config::GetGlConfig;
 use glutin::context::{ContextAttributesBuilder, GlProfile, PossiblyCurrentContext};
 use glutin::display::GetGlDisplay;
 use glutin::prelude::*;
-use glutin::surface::{Surface, WindowSurface};
+use glutin::surface::{Surface, SurfaceAttributesBuilder, WindowSurface};
 use glutin_winit::{DisplayBuilder, GlWindow};
 use winit::application::ApplicationHandler;
-use winit::event::{DeviceEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{DeviceEvent, MouseButton, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::KeyCode;
 use winit::raw_window_handle::HasWindowHandle;
 use winit::window::{CursorGrabMode, Window};
 
-use opengl_rust::input::InputManager;
-use opengl_rust::renderer::{RenderInfo, Renderer};
-use opengl_rust::ui::Ui;
-use opengl_rust::scene::Scene;
+use crate::clock::{Clock, SystemClock};
+use crate::input::{ActionMap, InputManager};
+use crate::renderer::shader::ShaderProgram;
+use crate::renderer::{Rect, RenderInfo, Renderer};
+use crate::ui::{MouseLookMode, Ui, BOOKMARK_SLOTS};
+use crate::scene::{Camera, Scene};
+
+// Fixed updates per second when `AppConfig::update_rate` isn't set
+const DEFAULT_UPDATE_RATE: u32 = 60;
+// Longest frame the simulation catches up on, anything longer is dropped to avoid stalling
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+// Consecutive swap failures tolerated before giving up
+const MAX_SWAP_FAILURES: u32 = 3;
+// Recall the camera bookmark of the same slot, or save it while Ctrl is held
+const BOOKMARK_KEYS: [KeyCode; BOOKMARK_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
 
 struct GfxData {
     surface: Surface<WindowSurface>,
@@ -29,33 +53,112 @@ struct GfxData {
     window: Window,
 }
 
+/// Builds the scene once the GL context exists, GL resources can't be created before that.
+/// Called again every time the scene is switched to.
+pub type SceneBuilder = dyn Fn(&mut Scene) -> Result<(), String>;
+
+/// Startup options for the application
+#[derive(Default)]
+pub struct AppConfig {
+    /// Directory where linked shader programs are cached between runs, `None` disables it
+    pub shader_cache_dir: Option<PathBuf>,
+    /// File the key bindings are loaded from and saved to when rebound, `None` keeps the defaults
+    pub key_bindings_file: Option<PathBuf>,
+    /// Fixed scene updates per second, independent of the frame rate. Defaults to 60.
+    pub update_rate: Option<u32>,
+    /// MSAA samples of the window surface, the closest count available is used. `Some(0)`
+    /// disables multisampling, `None` takes the most samples available.
+    pub msaa_samples: Option<u8>,
+    /// Print shader compile and link logs even on success, drivers report warnings there
+    pub verbose_shader_logs: bool,
+}
+
+/// Window, GL context and main loop around a `Renderer`, a `Scene` and the egui controls
 pub struct App {
+    config: AppConfig,
+    scenes: Vec<(String, Box<SceneBuilder>)>, // Name and builder, switched between at runtime
+    active_scene: usize,
     gfx_data: Option<GfxData>,
     renderer: Option<Renderer>,
     scene: Option<Scene>,
+    secondary_camera: Camera, // Fixed overview camera for the right half in split view
     gui: Ui,
     fps_counter: Fps,
     input_manager: InputManager,
-    start_time: Instant,
-    last_frame_time: Instant,
+    clock: Box<dyn Clock>,
+    last_frame_time: Duration,
+    time: Duration,        // Simulation time of the last fixed update, stops while paused
+    accumulator: Duration, // Time not yet consumed by fixed updates
+    update_step: Duration, // Simulated time per fixed update
+    swap_failures: u32, // Consecutive frames whose buffers couldn't be swapped
+    minimized: bool,    // The window has no area, nothing is rendered until it gets one back
+    pick_requested: Option<(f64, f64)>, // Cursor position of a click to select the object under
     exit_state: Result<(), Box<dyn Error>>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(
+        config: AppConfig,
+        build_scene: impl Fn(&mut Scene) -> Result<(), String> + 'static,
+    ) -> Self {
+        let mut secondary_camera = Camera::new();
+        secondary_camera.set_position(glam::Vec3::new(0.0, 8.0, 8.0));
+        secondary_camera.set_orientation(-90.0, -40.0);
+
+        let update_rate = config.update_rate.unwrap_or(DEFAULT_UPDATE_RATE).max(1);
+        let update_step = Duration::from_secs(1) / update_rate;
+
+        let mut input_manager = InputManager::default();
+        if let Some(path) = &config.key_bindings_file
+            && path.exists()
+        {
+            match ActionMap::load(path) {
+                Ok(map) => *input_manager.action_map_mut() = map,
+                Err(e) => println!("Failed to load key bindings from {}: {}", path.display(), e),
+            }
+        }
+
         App {
+            config,
+            scenes: vec![(String::from("Default"), Box::new(build_scene))],
+            active_scene: 0,
             gfx_data: None,
             renderer: None,
             scene: None,
+            secondary_camera,
             gui: Ui::default(),
             fps_counter: Fps::new(),
-            input_manager: InputManager::default(),
-            start_time: Instant::now(),
-            last_frame_time: Instant::now(),
+            input_manager,
+            clock: Box::new(SystemClock::new()),
+            last_frame_time: Duration::ZERO,
+            time: Duration::ZERO,
+            accumulator: Duration::ZERO,
+            update_step,
+            swap_failures: 0,
+            minimized: false,
+            pick_requested: None,
             exit_state: Ok(()),
         }
     }
 
+    /// Replaces the wall clock the frame times come from, e.g. with a `ManualClock` shared
+    /// through an `Rc` to step the application deterministically
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self.last_frame_time = self.clock.now();
+        self
+    }
+
+    /// Adds another scene that can be switched to with F4 or from the controls
+    pub fn with_scene(
+        mut self,
+        name: &str,
+        build_scene: impl Fn(&mut Scene) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.scenes.push((name.to_string(), Box::new(build_scene)));
+        self
+    }
+
     /**
      * Consumes the App and returns the exit state.
      */
@@ -63,44 +166,243 @@ impl App {
         self.exit_state
     }
 
-    fn render_and_swap(&mut self) {
+    fn render_and_swap(&mut self) -> Result<(), glutin::error::Error> {
+        if self.gui.rebinding.is_some() && !self.input_manager.is_capturing_key() {
+            self.input_manager.capture_next_key();
+        }
+        if self.gui.cursor_grab_toggle_requested {
+            self.gui.cursor_grab_toggle_requested = false;
+            self.toggle_cursor_grab();
+        }
+        if let Some(index) = self.gui.scene_switch_requested.take() {
+            self.switch_scene(index);
+        }
+
         if let Some(GfxData {
             surface,
             context,
+            cursor_grabbed,
             egui_glow,
             window,
             ..
         }) = self.gfx_data.as_mut()
         {
             self.fps_counter.update();
-            let now = Instant::now();
-            let dt = now.duration_since(self.last_frame_time);
-            let time = now.duration_since(self.start_time);
+            let now = self.clock.now();
+            self.input_manager.set_time(now);
+            // Saturating, a manual clock may be set back
+            let dt = now.saturating_sub(self.last_frame_time);
             self.last_frame_time = now;
+            // `dt` keeps following the wall clock so the camera still moves while paused
+            if !self.gui.paused {
+                self.accumulator += dt.min(MAX_FRAME_TIME);
+            } else if self.gui.step_requested {
+                self.accumulator += self.update_step;
+            }
+            self.gui.step_requested = false;
             self.gui.fps = self.fps_counter.fps;
+            self.gui.frame_time_ms = dt.as_secs_f32() * 1000.0;
+            self.gui.gpu_timings = self.renderer.as_mut().unwrap().gpu_timings();
+            self.gui.render_stats = self.renderer.as_mut().unwrap().take_stats();
+            self.gui.light_counts = self.renderer.as_ref().unwrap().light_counts();
+            self.gui.object_count = self.scene.as_ref().unwrap().objects.len();
+            self.gui.cursor_grabbed = *cursor_grabbed;
+            self.gui.key_bindings = self.input_manager.action_map().clone();
 
-            // Update the UI
+            // Update the UI, keeping egui's zoom in sync with the manual scale override
+            if egui_glow.egui_ctx.zoom_factor() != self.gui.ui_scale {
+                egui_glow.egui_ctx.set_zoom_factor(self.gui.ui_scale);
+            }
             egui_glow.run(window, |ctx| {
-                self.gui.run(ctx);
+                self.gui.run(ctx, self.scene.as_mut().unwrap());
             });
 
+            let size = window.inner_size();
+            let split_view = self.gui.split_view && size.width >= 2 && size.height > 0;
+            let split_width = size.width / 2;
+            let scene = self.scene.as_mut().unwrap();
+            if split_view {
+                scene.camera_mut().resize(split_width, size.height);
+                self.secondary_camera
+                    .resize(size.width - split_width, size.height);
+                self.secondary_camera.update_matrices();
+            } else if size.width > 0 && size.height > 0 {
+                scene.camera_mut().resize(size.width, size.height);
+            }
+
+            scene.selected_object = self.gui.selected_object;
+            // Picks what the last frame showed under the click, before anything moves
+            if let Some((x, y)) = self.pick_requested.take() {
+                let (x, y) = (x.max(0.0) as u32, y.max(0.0) as u32);
+                let renderer = self.renderer.as_mut().unwrap();
+                // Each half of the split view has its own camera
+                let picked = if split_view && x >= split_width {
+                    let width = size.width - split_width;
+                    renderer.pick(
+                        scene,
+                        &self.secondary_camera,
+                        width,
+                        size.height,
+                        x - split_width,
+                        y,
+                    )
+                } else {
+                    let width = if split_view { split_width } else { size.width };
+                    renderer.pick(scene, scene.camera(), width, size.height, x, y)
+                };
+                // Clicking the background clears the selection
+                self.gui.selected_object = picked.and_then(|id| scene.object_index(id));
+                scene.selected_object = self.gui.selected_object;
+            }
+            // The simulation runs at a fixed rate, frames show it between its last two steps
+            while self.accumulator >= self.update_step {
+                self.time += self.update_step;
+                self.accumulator -= self.update_step;
+                scene.fixed_update(self.time);
+            }
+            scene.interpolate(self.accumulator.as_secs_f32() / self.update_step.as_secs_f32());
+
             let render_info = RenderInfo {
                 dt,
-                time,
+                time: self.time,
                 input_manager: &self.input_manager,
                 ui: &self.gui,
             };
-
-            self.scene.as_mut().unwrap().update(&render_info);
+            scene.update(&render_info);
 
             let renderer = self.renderer.as_mut().unwrap();
-            renderer.render(self.scene.as_ref().unwrap(), &render_info);
+            renderer.set_depth_test(self.gui.depth_test);
+            renderer.set_depth_func(self.gui.depth_func);
+            renderer.set_front_face(self.gui.front_face);
+            renderer.set_face_culling(self.gui.face_culling);
+            renderer.set_depth_prepass(self.gui.depth_prepass);
+            renderer.set_debug_mode(self.gui.debug_mode);
+            renderer.set_polygon_offset(self.gui.polygon_offset());
+            if split_view {
+                let left = Rect::new(0, 0, split_width, size.height);
+                let right = Rect::new(split_width as i32, 0, size.width - split_width, size.height);
+                renderer.set_viewport(left);
+                renderer.set_scissor(Some(left));
+                renderer.render(scene, &render_info);
+                renderer.set_viewport(right);
+                renderer.set_scissor(Some(right));
+                renderer.render_from(scene, &self.secondary_camera, &render_info);
+
+                // Back to the whole window for the UI
+                renderer.set_scissor(None);
+                renderer.set_viewport(Rect::new(0, 0, size.width, size.height));
+            } else {
+                renderer.render(scene, &render_info);
+            }
 
             // Render UI on top of everything
+            renderer.begin_gpu_section("ui");
             egui_glow.paint(window);
+            renderer.end_gpu_section();
 
-            surface.swap_buffers(context).unwrap();
+            let swapped = surface.swap_buffers(context);
             self.input_manager.update();
+            return swapped;
+        }
+        Ok(())
+    }
+
+    // A lost context can't be recovered, anything else gets a new surface. Repeated failures
+    // end the application with the error.
+    fn handle_swap_error(&mut self, error: glutin::error::Error, event_loop: &ActiveEventLoop) {
+        self.swap_failures += 1;
+        let recovered = if error.error_kind() == glutin::error::ErrorKind::ContextLost
+            || self.swap_failures > MAX_SWAP_FAILURES
+        {
+            Err(error.into())
+        } else {
+            println!("Failed to swap buffers, recreating the surface: {}", error);
+            self.recreate_surface()
+        };
+        if let Err(e) = recovered {
+            println!("Unable to recover from swap failure: {}", e);
+            self.exit_state = Err(e);
+            event_loop.exit();
+        }
+    }
+
+    fn recreate_surface(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(gfx_data) = self.gfx_data.as_mut() else {
+            return Ok(());
+        };
+        let config = gfx_data.context.config();
+        let surface_attributes = gfx_data.window.build_surface_attributes(
+            SurfaceAttributesBuilder::new().with_srgb(Some(config.srgb_capable())),
+        )?;
+        let surface = unsafe {
+            config
+                .display()
+                .create_window_surface(&config, &surface_attributes)?
+        };
+        gfx_data.context.make_current(&surface)?;
+        surface.set_swap_interval(
+            &gfx_data.context,
+            glutin::surface::SwapInterval::Wait(NonZero::new(1).unwrap()),
+        )?;
+        gfx_data.surface = surface;
+        Ok(())
+    }
+
+    // Binds the key picked in the UI and saves the bindings, Escape cancels
+    fn finish_rebind(&mut self, key: KeyCode) {
+        let Some(action) = self.gui.rebinding.take() else {
+            return;
+        };
+        if key == KeyCode::Escape {
+            return;
+        }
+
+        let map = self.input_manager.action_map_mut();
+        self.gui.binding_error = map.bind(action, key).err();
+        if self.gui.binding_error.is_none()
+            && let Some(path) = &self.config.key_bindings_file
+            && let Err(e) = map.save(path)
+        {
+            println!("Failed to save key bindings to {}: {}", path.display(), e);
+        }
+    }
+
+    fn load_scene(&mut self, index: usize) -> Result<(), String> {
+        // Drop the old scene first so its GL resources are freed before the new ones are created
+        self.scene = None;
+        let mut scene = Scene::new();
+        (self.scenes[index].1)(&mut scene)?;
+
+        let size = self.gfx_data.as_ref().unwrap().window.inner_size();
+        if size.width > 0 && size.height > 0 {
+            for camera in scene.cameras_mut() {
+                camera.resize(size.width, size.height);
+            }
+        }
+        self.scene = Some(scene);
+        self.active_scene = index;
+        self.renderer.as_mut().unwrap().scene_changed();
+        self.gui.scene_changed(index);
+        Ok(())
+    }
+
+    fn switch_scene(&mut self, index: usize) {
+        let previous = self.active_scene;
+        if let Err(e) = self.load_scene(index) {
+            println!("Failed to load scene '{}': {}", self.scenes[index].0, e);
+            // The old scene is already gone, build it again
+            self.load_scene(previous).unwrap_or_else(|e| {
+                println!("Failed to reload scene '{}': {}", self.scenes[previous].0, e);
+                std::process::exit(1);
+            });
+        }
+    }
+
+    fn resize_viewport(&mut self, size: PhysicalSize<u32>) {
+        let renderer = self.renderer.as_mut().unwrap();
+        renderer.resize(size.width, size.height);
+        for camera in self.scene.as_mut().unwrap().cameras_mut() {
+            camera.resize(size.width, size.height);
         }
     }
 
@@ -140,11 +442,19 @@ impl ApplicationHandler for App {
         }
 
         let attributes = Window::default_attributes().with_title("OpenGL");
-        let template_builder = glutin::config::ConfigTemplateBuilder::new();
+        let template_builder = glutin::config::ConfigTemplateBuilder::new().with_stencil_size(8);
+        let msaa_samples = self.config.msaa_samples;
         let (window, config) = DisplayBuilder::new()
             .with_window_attributes(Some(attributes))
-            .build(event_loop, template_builder, |configs| {
-                configs
+            .build(event_loop, template_builder, |configs| match msaa_samples {
+                Some(samples) => configs
+                    // Ties go to the higher count
+                    .min_by_key(|config| {
+                        let available = config.num_samples();
+                        (available.abs_diff(samples), u8::MAX - available)
+                    })
+                    .unwrap(),
+                None => configs
                     .reduce(|accum, config| {
                         if config.num_samples() > accum.num_samples() {
                             config
@@ -152,14 +462,24 @@ impl ApplicationHandler for App {
                             accum
                         }
                     })
-                    .unwrap()
+                    .unwrap(),
             })
             .unwrap();
 
         let window = window.unwrap();
+        if let Some(samples) = msaa_samples
+            && samples != config.num_samples()
+        {
+            println!(
+                "{} MSAA samples requested, using {} instead",
+                samples,
+                config.num_samples()
+            );
+        }
         let raw_window_handle = window.window_handle().ok().map(|wh| wh.as_raw());
         let context_attributes = ContextAttributesBuilder::new()
             .with_profile(GlProfile::Core)
+            .with_debug(cfg!(feature = "gl-debug"))
             .build(raw_window_handle);
         let context = unsafe {
             config
@@ -168,7 +488,11 @@ impl ApplicationHandler for App {
                 .expect("Unable to create context")
         };
 
-        let surface_attributes = window.build_surface_attributes(Default::default()).unwrap();
+        let surface_attributes = window
+            .build_surface_attributes(
+                SurfaceAttributesBuilder::new().with_srgb(Some(config.srgb_capable())),
+            )
+            .unwrap();
         let surface = unsafe {
             config
                 .display()
@@ -185,7 +509,9 @@ impl ApplicationHandler for App {
                 config.display().get_proc_address(s.as_c_str())
             })
         };
-        let egui_glow = egui_glow::EguiGlow::new(event_loop, Arc::new(glow_ctx), None, None, true);
+        let scale_factor = window.scale_factor() as f32;
+        let egui_glow = egui_glow::EguiGlow::new(event_loop, Arc::new(glow_ctx), None, Some(scale_factor), true);
+        self.gui.display_scale = scale_factor;
         egui_glow.egui_ctx.set_theme(egui::Theme::Dark);
 
         self.gfx_data = Some(GfxData {
@@ -196,11 +522,23 @@ impl ApplicationHandler for App {
             window,
         });
         self.renderer = Some(Renderer::new(&config.display()));
-        self.scene = Some(Scene::new());
-        self.scene.as_mut().unwrap().init().unwrap_or_else(|e| {
+        let capabilities = self.renderer.as_ref().unwrap().capabilities().clone();
+        println!(
+            "OpenGL {} on {} ({})",
+            capabilities.version, capabilities.renderer, capabilities.vendor
+        );
+        self.gui.capabilities = Some(capabilities);
+        ShaderProgram::set_binary_cache_dir(self.config.shader_cache_dir.clone());
+        ShaderProgram::set_log_info_on_success(self.config.verbose_shader_logs);
+        self.renderer.as_mut().unwrap().init().unwrap_or_else(|e| {
             println!("Failed to initialize renderer: {}", e);
             std::process::exit(1);
         });
+        self.gui.scene_names = self.scenes.iter().map(|(name, _)| name.clone()).collect();
+        self.load_scene(self.active_scene).unwrap_or_else(|e| {
+            println!("Failed to initialize scene: {}", e);
+            std::process::exit(1);
+        });
     }
 
     fn window_event(
@@ -227,47 +565,105 @@ impl ApplicationHandler for App {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
-            WindowEvent::RedrawRequested => {
-                self.render_and_swap();
+            WindowEvent::RedrawRequested if self.minimized => {}
+            WindowEvent::RedrawRequested => match self.render_and_swap() {
+                Ok(()) => self.swap_failures = 0,
+                Err(e) => self.handle_swap_error(e, event_loop),
+            },
+            WindowEvent::Resized(size) if size.height == 0 || size.width == 0 => {
+                self.minimized = true;
             }
-            WindowEvent::Resized(size) if size.height > 0 && size.width > 0 => {
-                let renderer = self.renderer.as_mut().unwrap();
-                renderer.resize(size.width, size.height);
-                self.scene.as_mut().unwrap().camera.resize(size.width, size.height);
+            WindowEvent::Resized(size) => {
+                if self.minimized {
+                    self.minimized = false;
+                    // Don't count the time spent minimized as one long frame
+                    self.last_frame_time = self.clock.now();
+                }
+                self.resize_viewport(size);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // egui-winit already updated its native pixels-per-point from this event
+                println!("Scale factor changed to {:.2}", scale_factor);
+                self.gui.display_scale = scale_factor as f32;
+                // The physical size may change with the scale factor, and a Resized event isn't guaranteed
+                let size = self.gfx_data.as_ref().unwrap().window.inner_size();
+                if size.width > 0 && size.height > 0 {
+                    self.resize_viewport(size);
+                }
+                self.gfx_data.as_ref().unwrap().window.request_redraw();
             }
             WindowEvent::KeyboardInput { event, .. } => {
                 self.input_manager.process_key_event(&event);
+                if let Some(key) = self.input_manager.take_captured_key() {
+                    self.finish_rebind(key);
+                    return;
+                }
                 if self.input_manager.is_key_just_pressed(KeyCode::Escape) {
                     event_loop.exit();
                 }
                 if self.input_manager.is_key_just_pressed(KeyCode::AltLeft) {
                     self.toggle_cursor_grab();
                 }
+                if self.input_manager.is_key_just_pressed(KeyCode::KeyP) {
+                    self.gui.paused = !self.gui.paused;
+                }
+                if self.input_manager.is_key_just_pressed(KeyCode::F3) {
+                    self.gui.show_stats_overlay = !self.gui.show_stats_overlay;
+                }
+                if self.input_manager.is_key_just_pressed(KeyCode::F4) {
+                    self.switch_scene((self.active_scene + 1) % self.scenes.len());
+                }
+                if self.input_manager.is_key_just_pressed(KeyCode::Period) {
+                    self.gui.step_requested = true;
+                }
+                if let Some(slot) = BOOKMARK_KEYS
+                    .iter()
+                    .position(|key| self.input_manager.is_key_just_pressed(*key))
+                {
+                    let camera = self.scene.as_mut().unwrap().camera_mut();
+                    if self.input_manager.is_key_pressed(KeyCode::ControlLeft)
+                        || self.input_manager.is_key_pressed(KeyCode::ControlRight)
+                    {
+                        self.gui.save_bookmark(slot, camera);
+                    } else {
+                        self.gui.recall_bookmark(slot, camera);
+                    }
+                }
             }
             WindowEvent::CursorMoved { position, .. } => {
                 self.input_manager
                     .process_mouse_position(position.x, position.y);
             }
-            WindowEvent::MouseWheel {
-                delta: MouseScrollDelta::LineDelta(_, y),
-                ..
-            } => {
-                self.input_manager.process_mouse_wheel_scroll(y);
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.input_manager.process_mouse_wheel(delta);
             }
             WindowEvent::MouseInput { state, button, .. } => {
                 self.input_manager.process_mouse_button(button, state);
-                if self
-                    .input_manager
-                    .is_mouse_button_just_pressed(MouseButton::Right)
+                if self.gui.picking
+                    && !gfx_data.cursor_grabbed
+                    && self.input_manager.is_mouse_button_just_pressed(MouseButton::Left)
                 {
-                    gfx_data.cursor_grabbed = true;
-                    self.apply_cursor_grab();
-                } else if self
+                    self.pick_requested = Some(self.input_manager.mouse_position());
+                    gfx_data.window.request_redraw();
+                }
+                let pressed = self
                     .input_manager
-                    .is_mouse_button_just_released(MouseButton::Right)
-                {
-                    gfx_data.cursor_grabbed = false;
-                    self.apply_cursor_grab();
+                    .is_mouse_button_just_pressed(MouseButton::Right);
+                let released = self
+                    .input_manager
+                    .is_mouse_button_just_released(MouseButton::Right);
+                match self.gui.mouse_look_mode {
+                    // The cursor is grabbed for as long as the button is held
+                    MouseLookMode::Hold if pressed || released => {
+                        gfx_data.cursor_grabbed = pressed;
+                        self.apply_cursor_grab();
+                    }
+                    MouseLookMode::Toggle if pressed => {
+                        self.gui.mouse_look_toggled = !self.gui.mouse_look_toggled;
+                        gfx_data.cursor_grabbed = self.gui.mouse_look_toggled;
+                        self.apply_cursor_grab();
+                    }
+                    _ => {}
                 }
             }
             _ => {}
@@ -290,7 +686,9 @@ impl ApplicationHandler for App {
     }
 
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let Some(GfxData { window, .. }) = self.gfx_data.as_ref() {
+        if let Some(GfxData { window, .. }) = self.gfx_data.as_ref()
+            && !self.minimized
+        {
             window.request_redraw();
         }
     }
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use image::RgbImage;
+
+use crate::input::InputManager;
+use crate::renderer::framebuffer::{ColorFormat, Framebuffer};
+use crate::renderer::{RenderInfo, Renderer};
+use crate::scene::{Camera, Scene};
+use crate::ui::Ui;
+
+use gl::types::*;
+
+/// Window-less GL context with its own renderer, for rendering scenes straight to images
+/// (snapshot tests, thumbnails).
+///
+/// GL resources belong to the context that created them, so build the scene after creating
+/// this and keep it alive while the scene is used.
+pub struct Headless {
+    renderer: Renderer,
+    #[cfg(all(unix, not(target_os = "macos")))]
+    _context: glutin::api::egl::context::PossiblyCurrentContext,
+}
+
+impl Headless {
+    /// Creates a surfaceless EGL context on the first GPU device found. Fails when there is no
+    /// usable device, e.g. on a machine without GPU drivers.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn new() -> Result<Self, String> {
+        use glutin::api::egl::device::Device;
+        use glutin::api::egl::display::Display;
+        use glutin::config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder};
+        use glutin::context::{ContextApi, ContextAttributesBuilder, GlProfile, Version};
+        use glutin::prelude::*;
+
+        let device = Device::query_devices()
+            .map_err(|e| format!("Failed to query EGL devices: {}", e))?
+            .next()
+            .ok_or("No EGL device available")?;
+        let display = unsafe { Display::with_device(&device, None) }
+            .map_err(|e| format!("Failed to create EGL display: {}", e))?;
+
+        // No default framebuffer, everything is drawn to a `Framebuffer`
+        let template = ConfigTemplateBuilder::new()
+            .with_api(Api::OPENGL)
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("No OpenGL config available for headless rendering")?;
+
+        let context_attributes = ContextAttributesBuilder::new()
+            .with_profile(GlProfile::Core)
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(4, 5))))
+            .build(None);
+        let context = unsafe { display.create_context(&config, &context_attributes) }
+            .map_err(|e| format!("Failed to create headless context: {}", e))?
+            .make_current_surfaceless()
+            .map_err(|e| format!("Failed to make headless context current: {}", e))?;
+
+        let mut renderer = Renderer::new(&display);
+        renderer.init()?;
+        Ok(Self {
+            renderer,
+            _context: context,
+        })
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn new() -> Result<Self, String> {
+        Err("Headless rendering is only supported through EGL".to_string())
+    }
+
+    /// Renders `scene` from `camera` into a `width` x `height` image. The camera's aspect
+    /// ratio should match, see `Camera::resize`. The same scene always gives the same image,
+    /// except while deferred textures are loading, see `Texture2D::is_loaded`.
+    pub fn render_to_image(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+    ) -> Result<RgbImage, String> {
+        // sRGB target so the image gets the same gamma as the window
+        let framebuffer = Framebuffer::with_color_format(width, height, ColorFormat::Srgb8Alpha8)?;
+        framebuffer.bind();
+
+        // HDR would resolve to the default framebuffer, which doesn't exist here
+        let mut ui = Ui::default();
+        ui.hdr = false;
+        let input_manager = InputManager::default();
+        let render_info = RenderInfo {
+            dt: Duration::ZERO,
+            time: Duration::ZERO,
+            input_manager: &input_manager,
+            ui: &ui,
+        };
+        self.renderer.resize(width, height);
+        self.renderer.render_from(scene, camera, &render_info);
+
+        let mut pixels = vec![0u8; (width * height * 3) as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as GLsizei,
+                height as GLsizei,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+        framebuffer.unbind();
+
+        let mut image = RgbImage::from_raw(width, height, pixels)
+            .ok_or("Pixel buffer does not match the image size")?;
+        // GL rows start at the bottom
+        image::imageops::flip_vertical_in_place(&mut image);
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::renderer::FrontFace;
+    use crate::renderer::material::Material;
+    use crate::scene::Scene;
+    use crate::test_support;
+
+    #[test]
+    fn same_scene_renders_identically() {
+        let Some(mut headless) = test_support::context() else {
+            return;
+        };
+        let shader = test_support::unlit_shader();
+        let mut scene = Scene::new();
+        scene.register_mesh("quad", test_support::quad_mesh());
+        for (name, color) in [("red", [1.0, 0.0, 0.0]), ("green", [0.0, 1.0, 0.0])] {
+            let material = Material::unlit(Rc::clone(&shader), color);
+            scene.register_material(name, Rc::new(RefCell::new(material)));
+        }
+        // Overlapping quads alternating between materials, the draw order decides what's on top
+        for (i, material) in ["red", "green", "red", "green"].into_iter().enumerate() {
+            let object = scene.spawn("quad", material).unwrap();
+            let mut object = object.borrow_mut();
+            object.transform.position = glam::Vec3::new(i as f32 * 0.2 - 0.3, 0.0, -2.0);
+        }
+        let camera = test_support::camera(64, 48);
+
+        let first = headless.render_to_image(&scene, &camera, 64, 48).unwrap();
+        let second = headless.render_to_image(&scene, &camera, 64, 48).unwrap();
+        assert!(first.pixels().any(|pixel| pixel.0 != [0, 0, 0]), "Nothing was drawn");
+        assert_eq!(first.as_raw(), second.as_raw());
+    }
+
+    #[test]
+    fn culling_skips_faces_wound_the_other_way() {
+        let Some(mut headless) = test_support::context() else {
+            return;
+        };
+        let mut scene = Scene::new();
+        scene.register_mesh("quad", test_support::quad_mesh());
+        let material = Material::unlit(test_support::unlit_shader(), [1.0, 1.0, 1.0]);
+        scene.register_material("white", Rc::new(RefCell::new(material)));
+        let object = scene.spawn("quad", "white").unwrap();
+        object.borrow_mut().transform.position = glam::Vec3::new(0.0, 0.0, -1.0);
+        let camera = test_support::camera(32, 32);
+        let drawn = |headless: &mut super::Headless| {
+            let image = headless.render_to_image(&scene, &camera, 32, 32).unwrap();
+            image.pixels().any(|pixel| pixel.0 != [0, 0, 0])
+        };
+
+        // The quad is counter-clockwise as seen from the camera
+        assert!(drawn(&mut headless));
+        headless.renderer.set_face_culling(true);
+        assert!(drawn(&mut headless));
+        headless.renderer.set_front_face(FrontFace::Cw);
+        assert!(!drawn(&mut headless));
+        headless.renderer.set_face_culling(false);
+        assert!(drawn(&mut headless));
+    }
+}
@@ -1,15 +1,30 @@
+mod action_map;
+
 use std::collections::HashMap;
+use std::time::Duration;
 
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::event::{KeyEvent, MouseButton, ElementState};
+use winit::event::{KeyEvent, MouseButton, MouseScrollDelta, ElementState};
+
+pub use action_map::{Action, ActionMap};
+
+// Pixels of a trackpad scroll that amount to one notch of a mouse wheel
+const PIXELS_PER_LINE: f64 = 20.0;
 
 pub struct InputManager {
     keys: HashMap<KeyCode, bool>,
     just_pressed: HashMap<KeyCode, bool>,
     just_released: HashMap<KeyCode, bool>,
+    just_repeated: HashMap<KeyCode, bool>,
+    pressed_at: HashMap<KeyCode, Duration>, // When each held key went down, see `set_time`
+    now: Duration,                          // Time of the current frame on the app's clock
+    key_repeat: bool,                      // Report OS key repeats through `is_key_repeated`
+    action_map: ActionMap,
+    capturing: bool,               // The next key press is captured instead of registered
+    captured_key: Option<KeyCode>, // Taken with `take_captured_key`
     mouse_position: (f64, f64),  // Absolute position
     mouse_delta: (f64, f64),  // Relative position since last update call
-    mouse_wheel_delta: f32, // Scroll amount since last update call
+    mouse_wheel_delta: (f32, f32), // Scroll amount in lines since last update call
     mouse_buttons: HashMap<MouseButton, bool>,
     just_pressed_mouse_buttons: HashMap<MouseButton, bool>,
     just_released_mouse_buttons: HashMap<MouseButton, bool>,
@@ -21,9 +36,16 @@ impl InputManager {
             keys: HashMap::new(),
             just_pressed: HashMap::new(),
             just_released: HashMap::new(),
+            just_repeated: HashMap::new(),
+            pressed_at: HashMap::new(),
+            now: Duration::ZERO,
+            key_repeat: false,
+            action_map: ActionMap::new(),
+            capturing: false,
+            captured_key: None,
             mouse_position: (0.0, 0.0),
             mouse_delta: (0.0, 0.0),
-            mouse_wheel_delta: 0.0,
+            mouse_wheel_delta: (0.0, 0.0),
             mouse_buttons: HashMap::new(),
             just_pressed_mouse_buttons: HashMap::new(),
             just_released_mouse_buttons: HashMap::new(),
@@ -33,33 +55,67 @@ impl InputManager {
     pub fn update(&mut self) {
         self.just_pressed.clear();
         self.just_released.clear();
+        self.just_repeated.clear();
         self.mouse_delta = (0.0, 0.0);
-        self.mouse_wheel_delta = 0.0;
+        self.mouse_wheel_delta = (0.0, 0.0);
         self.just_pressed_mouse_buttons.clear();
         self.just_released_mouse_buttons.clear();
     }
 
-    pub fn process_key_event(&mut self, event: &KeyEvent) {
-        if event.repeat {
-            return;
-        }
+    /// Time of the current frame on the app's clock. Keys pressed during the frame are stamped
+    /// with it, and `key_held_duration` measures up to it.
+    pub fn set_time(&mut self, now: Duration) {
+        self.now = now;
+    }
 
+    pub fn process_key_event(&mut self, event: &KeyEvent) {
         let key = if let PhysicalKey::Code(key) = event.physical_key {
             key
         } else {
             return;
         };
 
-        match event.state {
-            winit::event::ElementState::Pressed => {
-                self.keys.insert(key, true);
-                self.just_pressed.insert(key, true);
-            }
-            winit::event::ElementState::Released => {
-                self.keys.insert(key, false);
-                self.just_released.insert(key, true);
+        if self.capturing && event.state == ElementState::Pressed && !event.repeat {
+            self.capturing = false;
+            self.captured_key = Some(key);
+            return;
+        }
+
+        // Repeats never count as new presses
+        if event.repeat {
+            if self.key_repeat {
+                self.just_repeated.insert(key, true);
             }
-        };
+            return;
+        }
+
+        self.set_key(key, event.state == ElementState::Pressed);
+    }
+
+    fn set_key(&mut self, key: KeyCode, pressed: bool) {
+        self.keys.insert(key, pressed);
+        if pressed {
+            self.just_pressed.insert(key, true);
+            self.pressed_at.insert(key, self.now);
+        } else {
+            self.just_released.insert(key, true);
+            self.pressed_at.remove(&key);
+        }
+    }
+
+    /// The next key press is stored for `take_captured_key` instead of being registered, to
+    /// let the user pick a key without triggering what it's bound to
+    pub fn capture_next_key(&mut self) {
+        self.capturing = true;
+        self.captured_key = None;
+    }
+
+    pub fn is_capturing_key(&self) -> bool {
+        self.capturing
+    }
+
+    pub fn take_captured_key(&mut self) -> Option<KeyCode> {
+        self.captured_key.take()
     }
 
     pub fn process_mouse_position(&mut self, x: f64, y: f64) {
@@ -70,8 +126,17 @@ impl InputManager {
         self.mouse_delta = (dx, dy);
     }
 
-    pub fn process_mouse_wheel_scroll(&mut self, dy: f32) {
-        self.mouse_wheel_delta += dy;
+    /// Accumulates mouse wheel and trackpad scrolling, pixel deltas are converted to lines
+    pub fn process_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let (dx, dy) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x, y),
+            MouseScrollDelta::PixelDelta(position) => (
+                (position.x / PIXELS_PER_LINE) as f32,
+                (position.y / PIXELS_PER_LINE) as f32,
+            ),
+        };
+        self.mouse_wheel_delta.0 += dx;
+        self.mouse_wheel_delta.1 += dy;
     }
 
     pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) {
@@ -100,6 +165,35 @@ impl InputManager {
         self.just_released.get(&key).copied().unwrap_or(false)
     }
 
+    pub fn is_action_pressed(&self, action: Action) -> bool {
+        self.is_key_pressed(self.action_map.key(action))
+    }
+
+    pub fn action_map(&self) -> &ActionMap {
+        &self.action_map
+    }
+
+    pub fn action_map_mut(&mut self) -> &mut ActionMap {
+        &mut self.action_map
+    }
+
+    /// Whether the OS sent a repeat for a held key since the last update, needs `set_key_repeat`
+    pub fn is_key_repeated(&self, key: KeyCode) -> bool {
+        self.just_repeated.get(&key).copied().unwrap_or(false)
+    }
+
+    pub fn set_key_repeat(&mut self, enabled: bool) {
+        self.key_repeat = enabled;
+    }
+
+    /// How long `key` has been held down as of the current frame, zero if it isn't pressed
+    pub fn key_held_duration(&self, key: KeyCode) -> Duration {
+        self.pressed_at
+            .get(&key)
+            .map(|pressed_at| self.now.saturating_sub(*pressed_at))
+            .unwrap_or(Duration::ZERO)
+    }
+
     pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
         self.mouse_buttons.get(&button).copied().unwrap_or(false)
     }
@@ -120,8 +214,14 @@ impl InputManager {
         self.mouse_delta
     }
 
+    /// Vertical scroll in lines, positive away from the user
     pub fn mouse_wheel_delta(&self) -> f32 {
-        self.mouse_wheel_delta
+        self.mouse_wheel_delta.1
+    }
+
+    /// Horizontal scroll in lines
+    pub fn mouse_wheel_delta_x(&self) -> f32 {
+        self.mouse_wheel_delta.0
     }
 }
 
@@ -130,3 +230,23 @@ impl Default for InputManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_duration_follows_the_frame_time() {
+        let mut input = InputManager::new();
+        input.set_time(Duration::from_secs(10));
+        assert_eq!(input.key_held_duration(KeyCode::KeyW), Duration::ZERO);
+
+        input.set_key(KeyCode::KeyW, true);
+        assert_eq!(input.key_held_duration(KeyCode::KeyW), Duration::ZERO);
+        input.set_time(Duration::from_millis(10_250));
+        assert_eq!(input.key_held_duration(KeyCode::KeyW), Duration::from_millis(250));
+
+        input.set_key(KeyCode::KeyW, false);
+        assert_eq!(input.key_held_duration(KeyCode::KeyW), Duration::ZERO);
+    }
+}
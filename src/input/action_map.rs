@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use winit::keyboard::KeyCode;
+
+/// Something the user can do that is bound to a key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+    ];
+
+    /// Name used in the bindings file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Action::MoveForward => KeyCode::KeyW,
+            Action::MoveBackward => KeyCode::KeyS,
+            Action::MoveLeft => KeyCode::KeyA,
+            Action::MoveRight => KeyCode::KeyD,
+            Action::MoveUp => KeyCode::KeyR,
+            Action::MoveDown => KeyCode::KeyF,
+        }
+    }
+}
+
+// Keys actions can be bound to, their debug names are what the bindings file stores
+const BINDABLE_KEYS: [KeyCode; 50] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::Tab,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+];
+
+/// Which key triggers each action, every action has exactly one key and keys aren't shared
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionMap {
+    bindings: HashMap<Action, KeyCode>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_key()))
+                .collect(),
+        }
+    }
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.bindings[&action]
+    }
+
+    /// Binds `key` to `action`, fails if the key can't be bound or another action uses it
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), String> {
+        if !BINDABLE_KEYS.contains(&key) {
+            return Err(format!("{:?} can't be bound", key));
+        }
+        if let Some((other, _)) = self
+            .bindings
+            .iter()
+            .find(|(other, bound)| **other != action && **bound == key)
+        {
+            return Err(format!("{:?} is already bound to {}", key, other.name()));
+        }
+        self.bindings.insert(action, key);
+        Ok(())
+    }
+
+    /// Reads `action = Key` lines, actions missing from the file keep their default key
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut bindings = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, key) = line
+                .split_once('=')
+                .ok_or(format!("Line {}: expected `action = Key`", number + 1))?;
+            let (name, key) = (name.trim(), key.trim());
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name)
+                .ok_or(format!("Line {}: unknown action '{}'", number + 1, name))?;
+            let key = BINDABLE_KEYS
+                .into_iter()
+                .find(|bindable| format!("{:?}", bindable) == key)
+                .ok_or(format!("Line {}: unknown key '{}'", number + 1, key))?;
+            bindings.insert(action, key);
+        }
+        for action in Action::ALL {
+            bindings.entry(action).or_insert(action.default_key());
+        }
+
+        let mut keys: Vec<KeyCode> = bindings.values().copied().collect();
+        keys.sort();
+        keys.dedup();
+        if keys.len() != bindings.len() {
+            return Err("Several actions are bound to the same key".to_string());
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text: String = Action::ALL
+            .iter()
+            .map(|action| format!("{} = {:?}\n", action.name(), self.key(*action)))
+            .collect();
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
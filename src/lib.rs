@@ -1,4 +1,10 @@
+pub mod app;
+pub mod clock;
+pub mod headless;
 pub mod renderer;
 pub mod scene;
 pub mod input;
 pub mod ui;
+
+#[cfg(test)]
+mod test_support;
@@ -1,52 +1,332 @@
+mod background;
 mod buffer;
+mod capabilities;
+pub mod framebuffer;
+mod gpu_timer;
+mod grid;
 pub mod material;
 pub mod mesh;
+pub mod post_process;
 pub mod shader;
+pub mod shader_variants;
 pub mod texture;
 
+pub use buffer::BufferUsage;
+pub use capabilities::Capabilities;
+pub use post_process::ToneMapping;
+
+use std::collections::HashSet;
 use std::ffi::CString;
+use std::mem::offset_of;
 use std::mem::MaybeUninit;
+use std::rc::Rc;
 use std::time::Duration;
 
 use glutin::display::GlDisplay;
 use winit::keyboard::KeyCode;
 
 use crate::input::InputManager;
-use crate::scene::Scene;
+use crate::scene::{Camera, Object, Scene};
 use crate::ui::Ui;
-use buffer::UniformBuffer;
+use background::Background;
+use buffer::{Std140Block, StorageBuffer, UniformBuffer};
+use framebuffer::{ColorFormat, Framebuffer, FramebufferSpec};
+use gpu_timer::GpuTimer;
+use grid::Grid;
+use post_process::PostProcess;
+use shader::ShaderProgram;
 
 use gl::types::*;
 
+/// Draws a `Scene` with the current GL context. Create it once the context is current, call
+/// `init`, then `render` every frame and `resize` whenever the window size changes.
 pub struct Renderer {
     wireframe: bool,
     flashlight: bool,
+    gamma_correction: bool,
+    depth_test: bool,
+    depth_func: DepthFunc,
+    front_face: FrontFace,
+    face_culling: bool, // Back faces of scene objects are culled
+    depth_prepass: bool,
+    debug_mode: DebugMode,
+    polygon_offset: Option<PolygonOffset>,
+    clear_alpha: f32,
+    clear_depth: f64,         // Value the depth buffer is cleared to
+    clear_depth_buffer: bool, // Whether the depth buffer is cleared at the start of a render
+    clear_stencil_buffer: bool,
     camera_ubo: UniformBuffer,
     light_ubo: UniformBuffer,
+    engine_ubo: UniformBuffer,
+    point_light_ssbo: StorageBuffer,
+    // Bytes last uploaded to the camera and light buffers, unchanged data isn't sent again
+    uploaded_camera: Vec<u8>,
+    uploaded_lights: Vec<u8>,
+    verified_programs: HashSet<GLuint>, // Programs whose uniform block layouts were checked
+    grid: Option<Grid>,
+    background: Option<Background>,
+    outline_shader: Option<ShaderProgram>, // Unlit, also draws the solid colored wireframe
+    debug_shader: Option<ShaderProgram>,
+    depth_shader: Option<ShaderProgram>,
+    pick_shader: Option<ShaderProgram>, // Writes object ids for `pick`
+    post_process: Option<PostProcess>,
+    hdr_framebuffer: Option<Framebuffer>, // Offscreen target the scene is drawn to when HDR is on
+    pick_framebuffer: Option<Framebuffer>, // Object ids, created on the first `pick`
+    gpu_timer: GpuTimer,
+    stats: RenderStats,
+    light_counts: LightCounts,
+    width: u32,
+    height: u32,
+    viewport: Rect,
+    scissor: Option<Rect>,
+    capabilities: Capabilities,
+}
+
+/// Work submitted by the renderer, accumulated until read with `Renderer::take_stats`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub buffer_uploads: u32, // Camera and light buffer updates, skipped when nothing changed
+}
+
+/// Lights sent to the shaders by the last render. Spot and directional lights past
+/// `MAX_SPOT_LIGHTS` and `MAX_DIRECTIONAL_LIGHTS` are dropped, in scene order.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LightCounts {
+    pub point: u32, // Area lights count once per point sample
+    pub spot: u32,
+    pub directional: u32,
+    pub dropped_spot: u32,
+    pub dropped_directional: u32,
+}
+
+impl LightCounts {
+    pub fn dropped(&self) -> u32 {
+        self.dropped_spot + self.dropped_directional
+    }
+}
+
+/// Pixel rectangle in window coordinates, origin at the bottom left
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// Comparison used by the depth and stencil tests
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    #[default]
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+pub type DepthFunc = CompareFunc;
+pub type StencilFunc = CompareFunc;
+
+/// Depth offset applied to filled polygons, see `glPolygonOffset`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: f32, // Scales the depth slope of the polygon
+    pub units: f32,  // Multiples of the smallest resolvable depth difference
+}
+
+/// Winding order of the vertices of front facing triangles, see `glFrontFace`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FrontFace {
+    #[default]
+    Ccw, // Counter-clockwise, the OpenGL default
+    Cw,  // Clockwise, used by some imported meshes
+}
+
+impl FrontFace {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            FrontFace::Ccw => gl::CCW,
+            FrontFace::Cw => gl::CW,
+        }
+    }
+}
+
+/// Replaces the lit materials with a visualization of a single attribute
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DebugMode {
+    #[default]
+    None,
+    Normals,  // World space normals as RGB
+    Depth,    // Linearized depth from black (near) to white (far plane)
+    Uvs,      // Texture coordinates as red and green
+    Overdraw, // Additive heatmap of how many times each pixel is drawn, ignoring depth
+}
+
+impl DebugMode {
+    fn as_uniform(&self) -> i32 {
+        match self {
+            DebugMode::None => 0,
+            DebugMode::Normals => 1,
+            DebugMode::Depth => 2,
+            DebugMode::Uvs => 3,
+            DebugMode::Overdraw => 4,
+        }
+    }
+}
+
+/// Action taken on the stencil buffer after the stencil/depth tests
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Increment => gl::INCR,
+            StencilOp::IncrementWrap => gl::INCR_WRAP,
+            StencilOp::Decrement => gl::DECR,
+            StencilOp::DecrementWrap => gl::DECR_WRAP,
+            StencilOp::Invert => gl::INVERT,
+        }
+    }
 }
 
 pub struct RenderInfo<'a> {
     pub dt: Duration,   // Time since the last frame
-    pub time: Duration, // Time since the start of the application
+    pub time: Duration, // Simulation time, advances in fixed steps and stops while paused
     pub input_manager: &'a InputManager,
     pub ui: &'a Ui,
 }
 
 impl Renderer {
+    /// Loads the GL function pointers from `display`, its context must be current
     pub fn new<D: GlDisplay>(display: &D) -> Self {
         gl::load_with(|s| {
             let s = CString::new(s).unwrap();
             display.get_proc_address(s.as_c_str())
         });
 
+        #[cfg(feature = "gl-debug")]
+        enable_debug_output();
+
+        let capabilities = Capabilities::query();
+        material::Material::set_max_texture_slots(capabilities.max_texture_units.max(1) as usize);
+
         Renderer {
             wireframe: false,
             flashlight: false,
+            gamma_correction: true,
+            depth_test: true,
+            depth_func: DepthFunc::default(),
+            front_face: FrontFace::default(),
+            face_culling: false,
+            depth_prepass: false,
+            debug_mode: DebugMode::default(),
+            polygon_offset: None,
+            clear_alpha: 1.0,
+            clear_depth: 1.0,
+            clear_depth_buffer: true,
+            clear_stencil_buffer: false,
             camera_ubo: UniformBuffer::new(0, std::mem::size_of::<CameraUniforms>()),
             light_ubo: UniformBuffer::new(1, std::mem::size_of::<LightUniforms>()),
+            engine_ubo: UniformBuffer::new(2, std::mem::size_of::<EngineUniforms>()),
+            point_light_ssbo: StorageBuffer::new(0),
+            uploaded_camera: Vec::new(),
+            uploaded_lights: Vec::new(),
+            verified_programs: HashSet::new(),
+            grid: None,
+            background: None,
+            outline_shader: None,
+            debug_shader: None,
+            depth_shader: None,
+            pick_shader: None,
+            post_process: None,
+            hdr_framebuffer: None,
+            pick_framebuffer: None,
+            gpu_timer: GpuTimer::new(),
+            stats: RenderStats::default(),
+            light_counts: LightCounts::default(),
+            width: 800,
+            height: 600,
+            viewport: Rect::new(0, 0, 800, 600),
+            scissor: None,
+            capabilities,
         }
     }
 
+    /// Driver information and limits, optional features should check these before use
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Loads the resources owned by the renderer itself, like the reference grid
+    pub fn init(&mut self) -> Result<(), String> {
+        self.grid = Some(Grid::new(1.0, 20.0)?);
+        self.background = Some(Background::new()?);
+        self.outline_shader = Some(ShaderProgram::from_files(
+            "./shaders/unlit.vs",
+            "./shaders/unlit.fs",
+        )?);
+        self.debug_shader = Some(ShaderProgram::from_files(
+            "./shaders/debug.vs",
+            "./shaders/debug.fs",
+        )?);
+        self.depth_shader = Some(ShaderProgram::from_files(
+            "./shaders/depth.vs",
+            "./shaders/depth.fs",
+        )?);
+        self.pick_shader = Some(ShaderProgram::from_files(
+            "./shaders/depth.vs",
+            "./shaders/pick.fs",
+        )?);
+        self.post_process = Some(PostProcess::new()?);
+        Ok(())
+    }
+
+    /// Handles the renderer hotkeys and draws the scene from its own camera
     pub fn render(&mut self, scene: &Scene, args: &RenderInfo) {
         let input = args.input_manager;
         if input.is_key_just_pressed(KeyCode::KeyL) {
@@ -56,50 +336,506 @@ impl Renderer {
             self.flashlight = !self.flashlight;
         }
 
-        let color = args.ui.clear_color;
+        self.render_from(scene, scene.camera(), args);
+    }
+
+    /// Draws the scene as seen from `camera` into the current viewport
+    pub fn render_from(&mut self, scene: &Scene, camera: &Camera, args: &RenderInfo) {
+        // Debug views are drawn straight to the target, without tone mapping
+        let debug_view = self.debug_mode != DebugMode::None && self.debug_shader.is_some();
+        let hdr = args.ui.hdr && !debug_view && self.prepare_hdr_framebuffer();
+        if hdr {
+            self.hdr_framebuffer.as_ref().unwrap().bind();
+        }
+        self.gpu_timer.begin("scene");
+
+        let outlined = scene
+            .selected_object
+            .filter(|_| self.outline_shader.is_some())
+            .and_then(|index| scene.objects.get(index))
+            .filter(|object| object.borrow().visible);
+
+        let overdraw = debug_view && self.debug_mode == DebugMode::Overdraw;
+        // Only the nearest surface gets shaded, which needs a depth test keeping the nearest one.
+        // The overdraw view tests depth too so it shows what the prepass saves.
+        let prepass = self.depth_prepass
+            && self.depth_shader.is_some()
+            && (overdraw
+                || (self.depth_test
+                    && matches!(self.depth_func, DepthFunc::Less | DepthFunc::LessEqual)));
+        // Wireframe ignores the materials, every object is drawn with the same solid color
+        let wireframe = self.wireframe && !debug_view && self.outline_shader.is_some();
+        let color = if overdraw { [0.0; 3] } else { args.ui.clear_color };
         unsafe {
-            gl::ClearColor(color[0], color[1], color[2], 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::Enable(gl::DEPTH_TEST);
+            gl::ClearColor(color[0], color[1], color[2], self.clear_alpha);
+        }
+        self.clear(
+            true,
+            self.clear_depth_buffer,
+            self.clear_stencil_buffer || outlined.is_some(),
+        );
+        if args.ui.gradient_background
+            && !overdraw
+            && let Some(background) = self.background.as_ref()
+        {
+            unsafe {
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            }
+            background.draw(args.ui.background_top, args.ui.background_bottom);
+            if self.wireframe {
+                unsafe {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                }
+            }
+        }
+        apply_face_culling(self.face_culling, self.front_face);
+        unsafe {
+            if self.depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthFunc(self.depth_func.as_gl_enum());
+            } else {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            // Every layer adds up, so nothing may be hidden by the depth test
+            if overdraw {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::ONE, gl::ONE);
+            }
+            // Debug colors are data, they are written without the sRGB conversion
+            if self.gamma_correction && !debug_view {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
         }
 
-        self.update_camera_buffer(scene);
+        self.update_camera_buffer(camera);
         self.update_light_parameters(scene);
+        self.update_engine_buffer(args);
+
+        let mut wireframe_lines = None;
+        if wireframe {
+            let [min, max] = self.capabilities.line_width_range;
+            unsafe {
+                gl::LineWidth(args.ui.wireframe_width.clamp(min, max));
+            }
+            wireframe_lines = Some(LineSmoothing::begin(args.ui.smooth_lines));
+        }
+
+        if prepass {
+            self.render_depth_prepass(scene);
+        }
+
+        // Render objects, the outlined one marks its pixels in the stencil buffer
+        if outlined.is_some() {
+            self.set_stencil_test(true);
+            self.set_stencil_op(StencilOp::Keep, StencilOp::Keep, StencilOp::Replace);
+        }
+        // Objects are grouped by material, consecutive ones don't bind it again
+        let mut bound_material = None;
+        for object in &scene.render_order() {
+            if let Some(outlined) = outlined {
+                let is_outlined = Rc::ptr_eq(object, outlined);
+                self.set_stencil_func(StencilFunc::Always, 1, 0xFF);
+                self.set_stencil_mask(if is_outlined { 0xFF } else { 0x00 });
+            }
+
+            let object = object
+                .try_borrow()
+                .expect("Object mutably borrowed while rendering");
+            if !object.visible {
+                continue;
+            }
+            // Scoped so the material is free again by the time the object renders with it
+            {
+                let material = object.material();
+                let material = material
+                    .try_borrow()
+                    .expect("Material mutably borrowed while rendering");
+                apply_polygon_offset(material.polygon_offset().or(self.polygon_offset));
+                if cfg!(debug_assertions) && !debug_view {
+                    self.verify_uniform_layouts(&material.shader());
+                }
+            }
+            if debug_view {
+                let shader = self.debug_shader.as_ref().unwrap();
+                shader.use_program();
+                shader.set_uniform_1i("mode", self.debug_mode.as_uniform());
+                shader.set_uniform_1f("near", camera.near());
+                shader.set_uniform_1f("far", camera.far());
+                shader.invalidate_uniform("normal_matrix");
+                shader.set_uniform_mat3("normal_matrix", &object.transform.normal_matrix());
+                object.draw_with(shader, &object.transform.model_matrix());
+            } else if wireframe {
+                let shader = self.outline_shader.as_ref().unwrap();
+                shader.use_program();
+                shader.set_uniform_3fv("color", &args.ui.wireframe_color);
+                shader.set_uniform_1i("useTexture", 0);
+                object.draw_with(shader, &object.transform.model_matrix());
+            } else {
+                let material = object.material();
+                if !bound_material.as_ref().is_some_and(|bound| Rc::ptr_eq(bound, &material)) {
+                    material
+                        .try_borrow()
+                        .expect("Material mutably borrowed while rendering")
+                        .bind();
+                }
+                object.render_instance();
+                bound_material = Some(material);
+            }
+            self.stats.draw_calls += 1;
+            self.stats.triangles += object.triangle_count();
+        }
+
+        apply_polygon_offset(None);
+        // Outlines, the grid and the post-process passes draw both sides
+        apply_face_culling(false, self.front_face);
+        if prepass {
+            unsafe {
+                gl::DepthMask(gl::TRUE);
+                gl::DepthFunc(self.depth_func.as_gl_enum());
+                if !self.depth_test {
+                    gl::Disable(gl::DEPTH_TEST);
+                }
+            }
+        }
+        if let Some(lines) = wireframe_lines {
+            unsafe {
+                gl::LineWidth(1.0);
+            }
+            lines.end();
+        }
+
+        if overdraw {
+            unsafe {
+                gl::Disable(gl::BLEND);
+                if self.depth_test {
+                    gl::Enable(gl::DEPTH_TEST);
+                }
+            }
+        }
+
+        if let Some(outlined) = outlined {
+            self.render_outline(&outlined.borrow(), args);
+        }
+
+        if args.ui.show_grid
+            && let Some(grid) = self.grid.as_mut()
+        {
+            grid.set_dimensions(args.ui.grid_spacing, args.ui.grid_extent);
+            let lines = LineSmoothing::begin(args.ui.smooth_lines);
+            grid.draw();
+            lines.end();
+        }
+        self.gpu_timer.end();
+
+        if hdr {
+            self.gpu_timer.begin("post-process");
+            let hdr_framebuffer = self.hdr_framebuffer.as_ref().unwrap();
+            hdr_framebuffer.unbind();
+            unsafe {
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            }
+            let post_process = self.post_process.as_mut().unwrap();
+            let mut bloom_intensity = None;
+            if args.ui.bloom {
+                match post_process.bloom(hdr_framebuffer, args.ui.bloom_threshold) {
+                    Ok(()) => bloom_intensity = Some(args.ui.bloom_intensity),
+                    Err(e) => println!("Failed to apply bloom: {}", e),
+                }
+            }
+            post_process.tonemap(
+                hdr_framebuffer,
+                self.viewport,
+                args.ui.exposure,
+                args.ui.tone_mapping,
+                bloom_intensity,
+            );
+            if self.wireframe {
+                unsafe {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                }
+            }
+            self.gpu_timer.end();
+        }
+    }
+
+    /// Scene draws since the last call, which resets the counters
+    pub fn take_stats(&mut self) -> RenderStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    pub fn light_counts(&self) -> LightCounts {
+        self.light_counts
+    }
+
+    /// Starts timing a named section on the GPU, e.g. the UI pass drawn outside the renderer
+    pub fn begin_gpu_section(&mut self, name: &'static str) {
+        self.gpu_timer.begin(name);
+    }
+
+    pub fn end_gpu_section(&mut self) {
+        self.gpu_timer.end();
+    }
+
+    /// GPU time in milliseconds of each timed section, a couple of frames old
+    pub fn gpu_timings(&mut self) -> Vec<(&'static str, f32)> {
+        self.gpu_timer.timings()
+    }
+
+    /// Id of the object seen at pixel (`x`, `y`) of a `width` x `height` view from `camera`,
+    /// counted from the top left, or `None` over the background. Draws the ids of the visible
+    /// objects into an integer target and reads the pixel back, so occlusion is exact.
+    pub fn pick(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+    ) -> Option<u32> {
+        if self.pick_shader.is_none() || x >= width || y >= height {
+            return None;
+        }
+        let outdated = match &self.pick_framebuffer {
+            Some(framebuffer) => framebuffer.width() != width || framebuffer.height() != height,
+            None => true,
+        };
+        if outdated {
+            let spec = FramebufferSpec {
+                stencil: false,
+                ..FramebufferSpec::new(width, height, ColorFormat::R32UI)
+            };
+            self.pick_framebuffer = match Framebuffer::from_spec(&spec) {
+                Ok(framebuffer) => Some(framebuffer),
+                Err(e) => {
+                    println!("Failed to create picking framebuffer: {}", e);
+                    return None;
+                }
+            };
+        }
+
+        // The scene may be drawn to another framebuffer, e.g. by the headless renderer
+        let mut previous_framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        }
+        self.pick_framebuffer.as_ref().unwrap().bind();
+        unsafe {
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::DepthMask(gl::TRUE);
+            gl::ClearBufferuiv(gl::COLOR, 0, [0u32; 4].as_ptr());
+            gl::ClearBufferfv(gl::DEPTH, 0, &1.0);
+        }
 
-        // Render objects
+        self.update_camera_buffer(camera);
+        // Culled faces can't be picked either
+        apply_face_culling(self.face_culling, self.front_face);
+        let shader = self.pick_shader.as_ref().unwrap();
+        shader.use_program();
+        // Hidden objects can't be picked
         for object in &scene.objects {
-            object.borrow().render();
+            let object = object
+                .try_borrow()
+                .expect("Object mutably borrowed while picking");
+            if !object.visible {
+                continue;
+            }
+            shader.set_uniform_1ui("id", object.id());
+            object.draw_with(shader, &object.transform.model_matrix());
+        }
+
+        // GL rows start at the bottom
+        let id = self.pick_framebuffer.as_ref().unwrap().read_uint(x, height - 1 - y);
+        apply_face_culling(false, self.front_face);
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+            if self.wireframe {
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+            }
+        }
+        self.set_viewport(self.viewport);
+        self.set_scissor(self.scissor);
+
+        match id {
+            Ok(0) => None,
+            Ok(id) => Some(id),
+            Err(e) => {
+                println!("Failed to read the picked object: {}", e);
+                None
+            }
+        }
+    }
+
+    // Fills the depth buffer with every object, then leaves depth writes off and the test on
+    // equal so the color pass shades each pixel once
+    fn render_depth_prepass(&mut self, scene: &Scene) {
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::DepthMask(gl::TRUE);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        }
+
+        let shader = self.depth_shader.as_ref().unwrap();
+        shader.use_program();
+        for object in &scene.render_order() {
+            let object = object
+                .try_borrow()
+                .expect("Object mutably borrowed while rendering");
+            if !object.visible {
+                continue;
+            }
+            // Same offset as the color pass, or the depths wouldn't be equal
+            let offset = object
+                .material()
+                .try_borrow()
+                .expect("Material mutably borrowed while rendering")
+                .polygon_offset();
+            apply_polygon_offset(offset.or(self.polygon_offset));
+            object.draw_with(shader, &object.transform.model_matrix());
+            self.stats.draw_calls += 1;
+            self.stats.triangles += object.triangle_count();
+        }
+        apply_polygon_offset(None);
+
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::DepthMask(gl::FALSE);
+            gl::DepthFunc(gl::EQUAL);
         }
     }
 
-    fn update_camera_buffer(&self, scene: &Scene) {
+    // Draws a scaled up, solid colored copy of `object` wherever it didn't write to the stencil
+    fn render_outline(&mut self, object: &Object, args: &RenderInfo) {
+        self.set_stencil_func(StencilFunc::NotEqual, 1, 0xFF);
+        self.set_stencil_mask(0x00);
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+
+        let shader = self.outline_shader.as_ref().unwrap();
+        let color = args.ui.outline_color;
+        let mut transform = object.transform.clone();
+        transform.scale *= 1.0 + args.ui.outline_width;
+        shader.use_program();
+        shader.set_uniform_3fv("color", &color);
+        shader.set_uniform_1i("useTexture", 0);
+        object.draw_with(shader, &transform.model_matrix());
+        self.stats.draw_calls += 1;
+        self.stats.triangles += object.triangle_count();
+
+        self.set_stencil_mask(0xFF);
+        self.set_stencil_func(StencilFunc::Always, 0, 0xFF);
+        self.set_stencil_test(false);
+        if self.depth_test {
+            unsafe {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+
+    // (Re)creates the HDR target if it doesn't match the window size, returns whether it's usable
+    fn prepare_hdr_framebuffer(&mut self) -> bool {
+        if self.post_process.is_none() {
+            return false;
+        }
+
+        let outdated = match &self.hdr_framebuffer {
+            Some(framebuffer) => {
+                framebuffer.width() != self.width || framebuffer.height() != self.height
+            }
+            None => true,
+        };
+        if outdated {
+            self.hdr_framebuffer = match Framebuffer::new(self.width, self.height) {
+                Ok(framebuffer) => Some(framebuffer),
+                Err(e) => {
+                    println!("Failed to create HDR framebuffer: {}", e);
+                    None
+                }
+            };
+        }
+        self.hdr_framebuffer.is_some()
+    }
+
+    // Checks once per program that the UBO structs match the std140 blocks in the shaders
+    fn verify_uniform_layouts(&mut self, program: &ShaderProgram) {
+        if !self.verified_programs.insert(program.id()) {
+            return;
+        }
+        UniformBuffer::verify_layout::<CameraUniforms>(program);
+        UniformBuffer::verify_layout::<LightUniforms>(program);
+        UniformBuffer::verify_layout::<EngineUniforms>(program);
+    }
+
+    // Changes every frame, so it's always uploaded
+    fn update_engine_buffer(&self, args: &RenderInfo) {
+        let resolution = [self.viewport.width as f32, self.viewport.height as f32];
+        self.engine_ubo
+            .map_data(0, 1, |data: &mut [EngineUniforms]| {
+                data[0] = EngineUniforms {
+                    resolution,
+                    time: args.time.as_secs_f32(),
+                    dt: args.dt.as_secs_f32(),
+                };
+            })
+            .expect("Couldn't update engine UBO");
+    }
+
+    fn update_camera_buffer(&mut self, camera: &Camera) {
+        let camera_uniforms = CameraUniforms {
+            view: *camera.view_matrix(),
+            projection: *camera.projection_matrix(),
+            view_pos: camera.position().extend(1.0),
+        };
+        let bytes = as_bytes(std::slice::from_ref(&camera_uniforms));
+        if self.uploaded_camera == bytes {
+            return;
+        }
+        self.uploaded_camera = bytes.to_vec();
+        self.stats.buffer_uploads += 1;
+
         self.camera_ubo
-            .map_data(0, 1, |camera: &mut [CameraUniforms]| {
-                camera[0].view = *scene.camera.view_matrix();
-                camera[0].projection = *scene.camera.projection_matrix();
-                camera[0].view_pos = scene.camera.position().extend(1.0);
+            .map_data(0, 1, |data: &mut [CameraUniforms]| {
+                data[0] = camera_uniforms;
             })
             .expect("Couldn't update camera UBO");
     }
 
-    fn update_light_parameters(&self, scene: &Scene) {
+    fn update_light_parameters(&mut self, scene: &Scene) {
         let mut light_uniforms = unsafe { MaybeUninit::<LightUniforms>::zeroed().assume_init() };
+        let mut point_lights = Vec::new();
+        let mut counts = LightCounts::default();
         for light in &scene.lights {
-            let light = light.borrow();
+            let light = light
+                .try_borrow()
+                .expect("Light mutably borrowed while rendering");
             let color = light.color;
+            let specular = light.specular();
             let position = light.position;
             if light.is_spot_light() {
                 let index = light_uniforms.nr_spot_lights as usize;
                 if index >= MAX_SPOT_LIGHTS {
-                    panic!("Exceeded maximum number of spot lights");
+                    counts.dropped_spot += 1;
+                    continue;
                 }
 
                 light_uniforms.spot[index].color = [color[0], color[1], color[2], 1.0];
+                light_uniforms.spot[index].specular = [specular[0], specular[1], specular[2], 1.0];
                 light_uniforms.spot[index].position = [position[0], position[1], position[2], 1.0];
                 light_uniforms.spot[index].intensity = light.intensity;
                 let light = light.as_spot_light().unwrap();
                 let direction = light.direction;
                 let attenuation = light.attenuation;
+                light_uniforms.spot[index].falloff = light.falloff.as_uniform();
                 light_uniforms.spot[index].direction =
                     [direction[0], direction[1], direction[2], 1.0];
                 light_uniforms.spot[index].inner_cutoff_cos = light.inner_cutoff_rad.cos();
@@ -108,27 +844,47 @@ impl Renderer {
                     [attenuation[0], attenuation[1], attenuation[2]];
                 light_uniforms.nr_spot_lights += 1;
             } else if light.is_point_light() {
-                let index = light_uniforms.nr_point_lights as usize;
-                if index >= MAX_POINT_LIGHTS {
-                    panic!("Exceeded maximum number of point lights");
-                }
-
-                light_uniforms.point[index].color = [color[0], color[1], color[2], 1.0];
-                light_uniforms.point[index].position = [position[0], position[1], position[2], 1.0];
-                light_uniforms.point[index].intensity = light.intensity;
-                let light = light.as_point_light().unwrap();
-                let attenuation = light.attenuation;
-                light_uniforms.point[index].attenuation =
-                    [attenuation[0], attenuation[1], attenuation[2]];
-                light_uniforms.nr_point_lights += 1;
+                let point = light.as_point_light().unwrap();
+                let attenuation = point.attenuation;
+                point_lights.push(PointLightUniforms {
+                    color: [color[0], color[1], color[2], 1.0],
+                    specular: [specular[0], specular[1], specular[2], 1.0],
+                    position: [position[0], position[1], position[2], 1.0],
+                    emit_direction: [0.0; 4],
+                    attenuation: [attenuation[0], attenuation[1], attenuation[2]],
+                    intensity: light.intensity,
+                    falloff: point.falloff.as_uniform(),
+                    _padding: [0; 3],
+                });
+            } else if light.is_area_light() {
+                // Split the intensity evenly between the point samples, which only light the
+                // side the quad faces
+                let area = light.as_area_light().unwrap();
+                let samples = area.sample_positions(position);
+                let intensity = light.intensity / samples.len() as f32;
+                let attenuation = area.attenuation;
+                let falloff = area.falloff.as_uniform();
+                let normal = area.normal.normalize_or(glam::Vec3::NEG_Y);
+                point_lights.extend(samples.into_iter().map(|sample| PointLightUniforms {
+                    color: [color[0], color[1], color[2], 1.0],
+                    specular: [specular[0], specular[1], specular[2], 1.0],
+                    position: [sample[0], sample[1], sample[2], 1.0],
+                    emit_direction: [normal[0], normal[1], normal[2], 1.0],
+                    attenuation: [attenuation[0], attenuation[1], attenuation[2]],
+                    intensity,
+                    falloff,
+                    _padding: [0; 3],
+                }));
             } else if light.is_directional_light() {
                 let index = light_uniforms.nr_directional_lights as usize;
                 if index >= MAX_DIRECTIONAL_LIGHTS {
-                    panic!("Exceeded maximum number of directional lights");
+                    counts.dropped_directional += 1;
+                    continue;
                 }
 
-                light_uniforms.directional[index].color =
-                    [light.color[0], light.color[1], light.color[2], 1.0];
+                light_uniforms.directional[index].color = [color[0], color[1], color[2], 1.0];
+                light_uniforms.directional[index].specular =
+                    [specular[0], specular[1], specular[2], 1.0];
                 light_uniforms.directional[index].intensity = light.intensity;
                 let light = light.as_directional_light().unwrap();
                 let direction = light.direction;
@@ -143,17 +899,182 @@ impl Renderer {
             [ambient.color[0], ambient.color[1], ambient.color[2], 1.0]
         };
         light_uniforms.ambient.intensity = scene.ambient_light.intensity;
+        light_uniforms.nr_point_lights = point_lights.len() as i32;
+        counts.point = point_lights.len() as u32;
+        counts.spot = light_uniforms.nr_spot_lights as u32;
+        counts.directional = light_uniforms.nr_directional_lights as u32;
+        self.light_counts = counts;
+
+        // Padding is zeroed, so equal bytes mean equal lights
+        let mut bytes = as_bytes(std::slice::from_ref(&light_uniforms)).to_vec();
+        bytes.extend_from_slice(as_bytes(&point_lights));
+        if self.uploaded_lights == bytes {
+            return;
+        }
+        self.uploaded_lights = bytes;
+        self.stats.buffer_uploads += 1;
 
         self.light_ubo
             .map_data(0, 1, |data: &mut [LightUniforms]| {
                 data[0] = light_uniforms;
             })
             .expect("Couldn't update light UBO");
+        self.point_light_ssbo.upload_data(&point_lights);
     }
 
+    /// Size of the window, or target framebuffer, in pixels. Resets the viewport to cover it.
     pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.set_viewport(Rect::new(0, 0, width, height));
+    }
+
+    /// Forgets state tied to the previous scene's GL objects, call it after switching scenes.
+    /// Deleted program names can be reused by the driver, so their layout checks are redone.
+    pub fn scene_changed(&mut self) {
+        self.verified_programs.clear();
+        self.uploaded_camera.clear();
+        self.uploaded_lights.clear();
+    }
+
+    /// Area of the window the next `render` calls draw to. `resize` resets it to the whole window.
+    pub fn set_viewport(&mut self, viewport: Rect) {
+        self.viewport = viewport;
         unsafe {
-            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::Viewport(
+                viewport.x,
+                viewport.y,
+                viewport.width as GLsizei,
+                viewport.height as GLsizei,
+            );
+        }
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// Restricts clears and draws to `scissor`, or lifts the restriction with `None`
+    pub fn set_scissor(&mut self, scissor: Option<Rect>) {
+        self.scissor = scissor;
+        unsafe {
+            match scissor {
+                Some(rect) => {
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(rect.x, rect.y, rect.width as GLsizei, rect.height as GLsizei);
+                }
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
+        }
+    }
+
+    /// Writes the final colors through the sRGB conversion of the default framebuffer,
+    /// so lighting is computed in linear space and displayed with the right gamma
+    pub fn set_gamma_correction(&mut self, enabled: bool) {
+        self.gamma_correction = enabled;
+    }
+
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+    }
+
+    pub fn set_depth_func(&mut self, func: DepthFunc) {
+        self.depth_func = func;
+    }
+
+    /// Which winding is considered front facing, for `set_face_culling` and `gl_FrontFacing`
+    pub fn set_front_face(&mut self, front_face: FrontFace) {
+        self.front_face = front_face;
+    }
+
+    /// Skips the back faces of scene objects, off by default since not every mesh is closed
+    pub fn set_face_culling(&mut self, enabled: bool) {
+        self.face_culling = enabled;
+    }
+
+    /// Draws the depth of every object before shading them, so hidden surfaces aren't shaded.
+    /// Only used with the `Less`/`LessEqual` depth functions.
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
+
+    pub fn set_debug_mode(&mut self, mode: DebugMode) {
+        self.debug_mode = mode;
+    }
+
+    /// Offset for every object whose material doesn't set its own, `None` disables it
+    pub fn set_polygon_offset(&mut self, offset: Option<PolygonOffset>) {
+        self.polygon_offset = offset;
+    }
+
+    pub fn set_clear_alpha(&mut self, alpha: f32) {
+        self.clear_alpha = alpha;
+    }
+
+    pub fn set_clear_depth(&mut self, depth: f64) {
+        self.clear_depth = depth;
+    }
+
+    /// Clears the chosen buffers of the current target right away, e.g. only depth between two
+    /// groups of draws so the second one is always on top. Color uses the last clear color.
+    pub fn clear(&mut self, color: bool, depth: bool, stencil: bool) {
+        let mut mask = 0;
+        if color {
+            mask |= gl::COLOR_BUFFER_BIT;
+        }
+        if depth {
+            mask |= gl::DEPTH_BUFFER_BIT;
+        }
+        if stencil {
+            mask |= gl::STENCIL_BUFFER_BIT;
+        }
+        if mask == 0 {
+            return;
+        }
+        unsafe {
+            gl::ClearDepth(self.clear_depth);
+            gl::Clear(mask);
+        }
+    }
+
+    /// Chooses which buffers besides color are cleared at the start of each render
+    pub fn set_clear_buffers(&mut self, depth: bool, stencil: bool) {
+        self.clear_depth_buffer = depth;
+        self.clear_stencil_buffer = stencil;
+    }
+
+    pub fn set_stencil_test(&mut self, enabled: bool) {
+        unsafe {
+            if enabled {
+                gl::Enable(gl::STENCIL_TEST);
+            } else {
+                gl::Disable(gl::STENCIL_TEST);
+            }
+        }
+    }
+
+    /// Stencil test passes when `func(reference & mask, stencil & mask)` holds
+    pub fn set_stencil_func(&mut self, func: StencilFunc, reference: i32, mask: u32) {
+        unsafe {
+            gl::StencilFunc(func.as_gl_enum(), reference, mask);
+        }
+    }
+
+    /// Actions for when the stencil test fails, the depth test fails, and both pass
+    pub fn set_stencil_op(&mut self, stencil_fail: StencilOp, depth_fail: StencilOp, pass: StencilOp) {
+        unsafe {
+            gl::StencilOp(
+                stencil_fail.as_gl_enum(),
+                depth_fail.as_gl_enum(),
+                pass.as_gl_enum(),
+            );
+        }
+    }
+
+    /// Bits of the stencil buffer that can be written
+    pub fn set_stencil_mask(&mut self, mask: u32) {
+        unsafe {
+            gl::StencilMask(mask);
         }
     }
 
@@ -176,32 +1097,80 @@ struct CameraUniforms {
     view_pos: glam::Vec4,
 }
 
+impl Std140Block for CameraUniforms {
+    const BLOCK_NAME: &'static str = "Camera";
+
+    fn members() -> Vec<(String, usize)> {
+        vec![
+            ("Camera.view".to_string(), offset_of!(CameraUniforms, view)),
+            ("Camera.projection".to_string(), offset_of!(CameraUniforms, projection)),
+            ("Camera.position".to_string(), offset_of!(CameraUniforms, view_pos)),
+        ]
+    }
+}
+
+/// Per frame values any shader can read by declaring this block:
+///
+/// ```glsl
+/// layout (std140, binding = 2) uniform Engine {
+///     vec2 resolution; // Size of the viewport in pixels
+///     float time;      // Simulation seconds, in fixed steps and stopped while paused
+///     float dt;        // Seconds since the last frame
+/// } engine;
+/// ```
+#[repr(C)]
+struct EngineUniforms {
+    resolution: [f32; 2],
+    time: f32,
+    dt: f32,
+}
+
+impl Std140Block for EngineUniforms {
+    const BLOCK_NAME: &'static str = "Engine";
+
+    fn members() -> Vec<(String, usize)> {
+        vec![
+            ("Engine.resolution".to_string(), offset_of!(EngineUniforms, resolution)),
+            ("Engine.time".to_string(), offset_of!(EngineUniforms, time)),
+            ("Engine.dt".to_string(), offset_of!(EngineUniforms, dt)),
+        ]
+    }
+}
+
 #[repr(C)]
 struct DirectionalLightUniforms {
     color: [f32; 4],
+    specular: [f32; 4],
     direction: [f32; 4],
     intensity: f32,
     _padding: [f32; 3],
 }
 
+// std430 element of the point light storage buffer
 #[repr(C)]
 struct PointLightUniforms {
     color: [f32; 4],
+    specular: [f32; 4],
     position: [f32; 4],
-    attenuation: [f32; 3], // constant, linear, quadratic
+    emit_direction: [f32; 4], // xyz faced by area light samples, w is 1 for them and 0 otherwise
+    attenuation: [f32; 3],    // constant, linear, quadratic
     intensity: f32,
+    falloff: i32, // Falloff::as_uniform
+    _padding: [i32; 3],
 }
 
 #[repr(C)]
 struct SpotLightUniforms {
     color: [f32; 4],
+    specular: [f32; 4],
     position: [f32; 4],
     direction: [f32; 4],
     inner_cutoff_cos: f32,
     outer_cutoff_cos: f32,
     attenuation: [f32; 3], // constant, linear, quadratic
     intensity: f32,
-    _padding: [f32; 2],
+    falloff: i32, // Falloff::as_uniform
+    _padding: [f32; 1],
 }
 
 #[repr(C)]
@@ -211,17 +1180,306 @@ struct AmbientLightUniforms {
     _padding: [f32; 3],
 }
 
-const MAX_POINT_LIGHTS: usize = 10;
-const MAX_SPOT_LIGHTS: usize = 5;
-const MAX_DIRECTIONAL_LIGHTS: usize = 5;
+/// Spot lights the shaders hold, the `MAX_SPOT_LIGHTS` define in basic_fragment.fs
+pub const MAX_SPOT_LIGHTS: usize = 5;
+/// Directional lights the shaders hold, the `MAX_DIRECTIONAL_LIGHTS` define in basic_fragment.fs
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 5;
 
 #[repr(C)]
 struct LightUniforms {
     ambient: AmbientLightUniforms,
     directional: [DirectionalLightUniforms; MAX_DIRECTIONAL_LIGHTS],
-    point: [PointLightUniforms; MAX_POINT_LIGHTS],
     spot: [SpotLightUniforms; MAX_SPOT_LIGHTS],
     nr_point_lights: i32,
     nr_spot_lights: i32,
-    nr_directional_lights: i32
+    nr_directional_lights: i32,
+}
+
+impl Std140Block for LightUniforms {
+    const BLOCK_NAME: &'static str = "LightData";
+
+    fn members() -> Vec<(String, usize)> {
+        let mut members = vec![
+            (
+                "LightData.ambient.color".to_string(),
+                offset_of!(LightUniforms, ambient.color),
+            ),
+            (
+                "LightData.ambient.intensity".to_string(),
+                offset_of!(LightUniforms, ambient.intensity),
+            ),
+            (
+                "LightData.nrPointLights".to_string(),
+                offset_of!(LightUniforms, nr_point_lights),
+            ),
+            (
+                "LightData.nrSpotLights".to_string(),
+                offset_of!(LightUniforms, nr_spot_lights),
+            ),
+            (
+                "LightData.nrDirectionalLights".to_string(),
+                offset_of!(LightUniforms, nr_directional_lights),
+            ),
+        ];
+
+        for i in 0..MAX_DIRECTIONAL_LIGHTS {
+            let base = offset_of!(LightUniforms, directional)
+                + i * std::mem::size_of::<DirectionalLightUniforms>();
+            let name = format!("LightData.directionalLights[{}]", i);
+            members.extend([
+                (format!("{name}.color"), base + offset_of!(DirectionalLightUniforms, color)),
+                (format!("{name}.specular"), base + offset_of!(DirectionalLightUniforms, specular)),
+                (format!("{name}.direction"), base + offset_of!(DirectionalLightUniforms, direction)),
+                (format!("{name}.intensity"), base + offset_of!(DirectionalLightUniforms, intensity)),
+            ]);
+        }
+
+        for i in 0..MAX_SPOT_LIGHTS {
+            let base =
+                offset_of!(LightUniforms, spot) + i * std::mem::size_of::<SpotLightUniforms>();
+            let attenuation = base + offset_of!(SpotLightUniforms, attenuation);
+            let name = format!("LightData.spotLights[{}]", i);
+            members.extend([
+                (format!("{name}.color"), base + offset_of!(SpotLightUniforms, color)),
+                (format!("{name}.specular"), base + offset_of!(SpotLightUniforms, specular)),
+                (format!("{name}.position"), base + offset_of!(SpotLightUniforms, position)),
+                (format!("{name}.direction"), base + offset_of!(SpotLightUniforms, direction)),
+                (
+                    format!("{name}.cutOff_cos"),
+                    base + offset_of!(SpotLightUniforms, inner_cutoff_cos),
+                ),
+                (
+                    format!("{name}.outerCutOff_cos"),
+                    base + offset_of!(SpotLightUniforms, outer_cutoff_cos),
+                ),
+                (format!("{name}.constant"), attenuation),
+                (format!("{name}.linear"), attenuation + 4),
+                (format!("{name}.quadratic"), attenuation + 8),
+                (format!("{name}.intensity"), base + offset_of!(SpotLightUniforms, intensity)),
+                (format!("{name}.falloff"), base + offset_of!(SpotLightUniforms, falloff)),
+            ]);
+        }
+
+        members
+    }
+}
+
+// Registers a callback logging every message of a debug context, no-op on other contexts
+#[cfg(feature = "gl-debug")]
+fn enable_debug_output() {
+    let mut flags = 0;
+    unsafe {
+        gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+    }
+    if flags as GLuint & gl::CONTEXT_FLAG_DEBUG_BIT == 0 {
+        println!("GL debug output requested but the context is not a debug context");
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Report from inside the failing call so the stack trace points at it
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+    }
+}
+
+#[cfg(feature = "gl-debug")]
+extern "system" fn debug_message_callback(
+    source: GLenum,
+    type_: GLenum,
+    _id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut std::ffi::c_void,
+) {
+    let source = match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let type_ = match type_ {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    };
+    let severity = match severity {
+        gl::DEBUG_SEVERITY_HIGH => "high",
+        gl::DEBUG_SEVERITY_MEDIUM => "medium",
+        gl::DEBUG_SEVERITY_LOW => "low",
+        _ => "notification",
+    };
+    let message = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+    println!(
+        "GL [{}] {} ({}): {}",
+        severity,
+        type_,
+        source,
+        String::from_utf8_lossy(message)
+    );
+}
+
+// Raw bytes of plain `#[repr(C)]` uniform data
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+}
+
+// Antialiases lines by writing their coverage to alpha, which needs blending to show.
+// The renderer otherwise draws without blending, so turning it off disables both.
+// Antialiased lines blend their edges, the blend state from before `begin` comes back on `end`
+struct LineSmoothing {
+    blend: bool,
+    blend_func: [GLint; 4], // Source and destination factors for RGB, then alpha
+}
+
+impl LineSmoothing {
+    fn begin(enabled: bool) -> Self {
+        let mut blend_func = [0; 4];
+        let blend = unsafe {
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut blend_func[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut blend_func[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut blend_func[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut blend_func[3]);
+            gl::IsEnabled(gl::BLEND) == gl::TRUE
+        };
+        if enabled {
+            unsafe {
+                gl::Enable(gl::LINE_SMOOTH);
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            }
+        }
+        Self { blend, blend_func }
+    }
+
+    fn end(self) {
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func.map(|f| f as GLenum);
+        unsafe {
+            gl::Disable(gl::LINE_SMOOTH);
+            gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            if self.blend {
+                gl::Enable(gl::BLEND);
+            } else {
+                gl::Disable(gl::BLEND);
+            }
+        }
+    }
+}
+
+fn apply_face_culling(enabled: bool, front_face: FrontFace) {
+    unsafe {
+        gl::FrontFace(front_face.as_gl_enum());
+        if enabled {
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+        } else {
+            gl::Disable(gl::CULL_FACE);
+        }
+    }
+}
+
+fn apply_polygon_offset(offset: Option<PolygonOffset>) {
+    unsafe {
+        match offset {
+            Some(offset) => {
+                gl::Enable(gl::POLYGON_OFFSET_FILL);
+                gl::PolygonOffset(offset.factor, offset.units);
+            }
+            None => gl::Disable(gl::POLYGON_OFFSET_FILL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::headless::Headless;
+    use crate::renderer::material::{Material, PropertiesMap};
+    use crate::renderer::texture::{Texture2D, TextureConfig};
+    use crate::scene::light::Light;
+    use crate::test_support;
+
+    // Whether blending is on, and its RGB source and destination factors
+    fn blend_state() -> (bool, GLint, GLint) {
+        let (mut src, mut dst) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut src);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut dst);
+            (gl::IsEnabled(gl::BLEND) == gl::TRUE, src, dst)
+        }
+    }
+
+    #[test]
+    fn line_smoothing_restores_the_blend_state() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        // Additive blending, like the overdraw view
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+        }
+        let additive = blend_state();
+        for enabled in [true, false] {
+            LineSmoothing::begin(enabled).end();
+            assert_eq!(blend_state(), additive);
+        }
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+        LineSmoothing::begin(true).end();
+        assert!(!blend_state().0);
+    }
+
+    // Brightness at the center of a white phong quad facing the camera, lit only by an area
+    // light between them whose quad faces `normal`
+    fn area_lit_brightness(headless: &mut Headless, normal: glam::Vec3) -> u8 {
+        let mut scene = Scene::new();
+        scene.register_mesh("quad", test_support::quad_mesh());
+        let shader = Rc::new(
+            ShaderProgram::from_files("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+                .unwrap(),
+        );
+        let white =
+            Rc::new(Texture2D::new_from_pixels(1, 1, &[255; 3], &TextureConfig::default()).unwrap());
+        let mut properties = PropertiesMap::new();
+        properties.set_texture("material.diffuse", Rc::clone(&white));
+        properties.set_texture("material.specular", white);
+        properties.set_integer("material.shininess", 32);
+        let material = Material::new_with_properties("phong", shader, properties);
+        scene.register_material("phong", Rc::new(RefCell::new(material)));
+        let quad = scene.spawn("quad", "phong").unwrap();
+        quad.borrow_mut().transform.position = glam::vec3(0.0, 0.0, -3.0);
+        quad.borrow_mut().transform.scale = glam::Vec3::splat(4.0);
+
+        let mut light = Light::new_area_light(0.5, 0.5);
+        light.position = glam::vec3(0.0, 0.0, -2.0);
+        light.as_area_light_mut().unwrap().normal = normal;
+        scene.add_light(Rc::new(RefCell::new(light)));
+
+        let image = headless
+            .render_to_image(&scene, &test_support::camera(16, 16), 16, 16)
+            .unwrap();
+        image.get_pixel(8, 8).0[0]
+    }
+
+    #[test]
+    fn area_lights_only_light_the_side_they_face() {
+        let Some(mut headless) = test_support::context() else {
+            return;
+        };
+        assert!(area_lit_brightness(&mut headless, glam::Vec3::NEG_Z) > 100);
+        assert_eq!(area_lit_brightness(&mut headless, glam::Vec3::Z), 0);
+    }
 }
@@ -0,0 +1,45 @@
+use gl::types::*;
+
+use crate::renderer::shader::ShaderProgram;
+
+/// Vertical color gradient drawn behind the scene instead of the flat clear color
+pub struct Background {
+    shader: ShaderProgram,
+    vao: GLuint, // Empty, the fullscreen triangle is generated in the vertex shader
+}
+
+impl Background {
+    pub fn new() -> Result<Self, String> {
+        let shader = ShaderProgram::from_files("./shaders/fullscreen.vs", "./shaders/gradient.fs")?;
+
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        Ok(Self { shader, vao })
+    }
+
+    /// Fills the viewport without touching the depth buffer, leaves depth testing disabled
+    pub fn draw(&self, top: [f32; 3], bottom: [f32; 3]) {
+        self.shader.use_program();
+        self.shader.set_uniform_3fv("topColor", &top);
+        self.shader.set_uniform_3fv("bottomColor", &bottom);
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -1,10 +1,15 @@
+use std::cell::Cell;
+
 use gl::types::*;
 
+use crate::renderer::shader::ShaderProgram;
+
 #[derive(Copy, Clone)]
 pub enum BufferType {
     Vertex,
     Index,
     Uniform,
+    Storage,
 }
 
 impl BufferType {
@@ -13,6 +18,26 @@ impl BufferType {
             BufferType::Vertex => gl::ARRAY_BUFFER,
             BufferType::Index => gl::ELEMENT_ARRAY_BUFFER,
             BufferType::Uniform => gl::UNIFORM_BUFFER,
+            BufferType::Storage => gl::SHADER_STORAGE_BUFFER,
+        }
+    }
+}
+
+/// How often the contents of a buffer are expected to change
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BufferUsage {
+    #[default]
+    Static, // Uploaded once, drawn many times
+    Dynamic, // Updated repeatedly, drawn many times
+    Stream, // Updated every frame, drawn a few times
+}
+
+impl BufferUsage {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
         }
     }
 }
@@ -20,6 +45,8 @@ impl BufferType {
 pub struct Buffer {
     id: GLuint,
     ty: GLenum,
+    usage: Cell<BufferUsage>, // Hint used by the last upload, reused on re-uploads
+    size: Cell<usize>,        // Allocated size in bytes
 }
 
 impl Buffer {
@@ -31,21 +58,54 @@ impl Buffer {
         Buffer {
             id,
             ty: type_.as_gl_enum(),
+            usage: Cell::new(BufferUsage::default()),
+            size: Cell::new(0),
         }
     }
 
+    /// Uploads `data` reusing the usage hint of the previous upload (static by default)
     pub fn upload_data<T>(&self, data: &[T]) {
+        self.upload_data_with_usage(data, self.usage.get());
+    }
+
+    pub fn upload_data_with_usage<T>(&self, data: &[T], usage: BufferUsage) {
+        self.usage.set(usage);
+        self.size.set(size_of_val(data));
         self.bind();
         unsafe {
             gl::BufferData(
                 self.ty,
                 size_of_val(data) as GLsizeiptr,
                 data.as_ptr() as *const _,
-                gl::STATIC_DRAW,
+                usage.as_gl_enum(),
             );
         }
     }
 
+    /// Overwrites part of the existing data store without reallocating it
+    pub fn update_data<T>(&self, offset: usize, data: &[T]) -> Result<(), String> {
+        let end = offset + size_of_val(data);
+        if end > self.size.get() {
+            return Err(format!(
+                "Update of {} bytes at offset {} exceeds buffer size of {} bytes",
+                size_of_val(data),
+                offset,
+                self.size.get()
+            ));
+        }
+
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                self.ty,
+                offset as GLintptr,
+                size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const _,
+            );
+        }
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub fn bind(&self) {
         unsafe {
@@ -69,6 +129,17 @@ impl Drop for Buffer {
     }
 }
 
+/// Describes how a `#[repr(C)]` struct maps onto a std140 uniform block in GLSL.
+pub trait Std140Block: Sized {
+    /// Name of the uniform block as declared in the shaders (e.g. `Camera`)
+    const BLOCK_NAME: &'static str;
+
+    /// Fully qualified GLSL member names (e.g. `Camera.view`) and the byte offset
+    /// of the matching field in the Rust struct.
+    fn members() -> Vec<(String, usize)>;
+}
+
+#[allow(dead_code)]
 pub struct UniformBuffer {
     binding_point: GLuint,
     buffer: Buffer,
@@ -94,6 +165,7 @@ impl UniformBuffer {
         }
     }
 
+    #[allow(dead_code)]
     pub fn update_data<T>(&self, offset: usize, data: &[T]) {
         self.buffer.bind();
         unsafe {
@@ -137,6 +209,38 @@ impl UniformBuffer {
         Ok(())
     }
 
+    /// Compares the std140 layout the driver computed for `T::BLOCK_NAME` in `program`
+    /// against the Rust struct, panicking on the first mismatch. Programs that don't
+    /// declare the block are skipped. Only meant to be called in debug builds.
+    pub fn verify_layout<T: Std140Block>(program: &ShaderProgram) {
+        let Some(block_size) = program.uniform_block_size(T::BLOCK_NAME) else {
+            return;
+        };
+
+        // The block size includes the padding up to a vec4 boundary, the struct may leave it out
+        let rust_size = std::mem::size_of::<T>();
+        if block_size != rust_size.next_multiple_of(16) {
+            panic!(
+                "std140 size mismatch for uniform block '{}': GLSL is {} bytes, Rust struct is {} bytes",
+                T::BLOCK_NAME,
+                block_size,
+                rust_size
+            );
+        }
+
+        for (name, rust_offset) in T::members() {
+            if let Some(offset) = program.uniform_block_member_offset(&name)
+                && offset != rust_offset
+            {
+                panic!(
+                    "std140 offset mismatch for '{}': GLSL offset is {}, Rust offset is {}",
+                    name, offset, rust_offset
+                );
+            }
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn bind(&self) {
         self.buffer.bind();
         unsafe {
@@ -144,7 +248,49 @@ impl UniformBuffer {
         }
     }
 
+    #[allow(dead_code)]
     pub fn unbind(&self) {
         self.buffer.unbind();
     }
 }
+
+/// Shader storage buffer whose size follows the data uploaded to it, for arrays
+/// that are sized at runtime (e.g. `buffer Foo { Item items[]; }` in GLSL).
+pub struct StorageBuffer {
+    binding_point: GLuint,
+    buffer: Buffer,
+}
+
+impl StorageBuffer {
+    pub fn new(binding_point: GLuint) -> Self {
+        StorageBuffer {
+            binding_point,
+            buffer: Buffer::new(BufferType::Storage),
+        }
+    }
+
+    /// Replaces the whole contents of the buffer, reallocating it to fit `data`.
+    /// An empty slice still allocates one zeroed element so the binding stays valid.
+    pub fn upload_data<T>(&self, data: &[T]) {
+        let size = size_of_val(data).max(std::mem::size_of::<T>());
+        self.buffer.bind();
+        unsafe {
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                size as isize,
+                std::ptr::null(),
+                BufferUsage::Dynamic.as_gl_enum(),
+            );
+            if !data.is_empty() {
+                gl::BufferSubData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    0,
+                    size_of_val(data) as isize,
+                    data.as_ptr() as *const _,
+                );
+            }
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.binding_point, self.buffer.id);
+        }
+        self.buffer.unbind();
+    }
+}
@@ -0,0 +1,96 @@
+use std::ffi::CStr;
+
+use gl::types::*;
+
+// GL_MAX_TEXTURE_MAX_ANISOTROPY, core since 4.6 and shared with the EXT/ARB extensions
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+/// Driver information and limits of the current context, queried once when the renderer is created
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    pub version: String,
+    pub renderer: String,
+    pub vendor: String,
+    pub gl_version: (i32, i32), // Major and minor version of the context
+    pub max_texture_units: i32, // Combined over all shader stages
+    pub max_uniform_block_size: i32, // In bytes
+    pub max_anisotropy: Option<f32>, // None when anisotropic filtering isn't supported
+    pub line_width_range: [f32; 2], // Supported by the driver for aliased lines
+    pub compute_shaders: bool,
+    pub storage_buffers: bool,
+}
+
+impl Capabilities {
+    /// Queries the current context, the GL function pointers must already be loaded
+    pub fn query() -> Self {
+        let extensions = extensions();
+        let has_extension = |name: &str| extensions.iter().any(|e| e == name);
+
+        let mut major = 0;
+        let mut minor = 0;
+        let mut max_texture_units = 0;
+        let mut max_uniform_block_size = 0;
+        let mut line_width_range = [1.0; 2];
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+            gl::GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut max_texture_units);
+            gl::GetIntegerv(gl::MAX_UNIFORM_BLOCK_SIZE, &mut max_uniform_block_size);
+            gl::GetFloatv(gl::ALIASED_LINE_WIDTH_RANGE, line_width_range.as_mut_ptr());
+        }
+        let at_least = |m: i32, n: i32| (major, minor) >= (m, n);
+
+        let max_anisotropy = if at_least(4, 6)
+            || has_extension("GL_ARB_texture_filter_anisotropic")
+            || has_extension("GL_EXT_texture_filter_anisotropic")
+        {
+            let mut max = 1.0;
+            unsafe {
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+            }
+            Some(max)
+        } else {
+            None
+        };
+
+        Self {
+            version: get_string(gl::VERSION),
+            renderer: get_string(gl::RENDERER),
+            vendor: get_string(gl::VENDOR),
+            gl_version: (major, minor),
+            max_texture_units,
+            max_uniform_block_size,
+            max_anisotropy,
+            line_width_range,
+            compute_shaders: at_least(4, 3) || has_extension("GL_ARB_compute_shader"),
+            storage_buffers: at_least(4, 3) || has_extension("GL_ARB_shader_storage_buffer_object"),
+        }
+    }
+}
+
+fn get_string(name: GLenum) -> String {
+    let ptr = unsafe { gl::GetString(name) };
+    if ptr.is_null() {
+        return String::from("unknown");
+    }
+    unsafe { CStr::from_ptr(ptr as *const _) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn extensions() -> Vec<String> {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+    (0..count.max(0) as GLuint)
+        .filter_map(|i| {
+            let ptr = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+            (!ptr.is_null()).then(|| {
+                unsafe { CStr::from_ptr(ptr as *const _) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+        })
+        .collect()
+}
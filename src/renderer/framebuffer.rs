@@ -0,0 +1,288 @@
+use gl::types::*;
+
+/// Storage of the color texture of a framebuffer
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorFormat {
+    Rgba8,
+    Srgb8Alpha8, // Colors written in linear space are stored as sRGB
+    #[default]
+    Rgba16F, // Floating point, for HDR
+    R32F,
+    R32UI, // Unsigned integers, e.g. object ids
+}
+
+impl ColorFormat {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            ColorFormat::Rgba8 => gl::RGBA8,
+            ColorFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            ColorFormat::Rgba16F => gl::RGBA16F,
+            ColorFormat::R32F => gl::R32F,
+            ColorFormat::R32UI => gl::R32UI,
+        }
+    }
+
+    // Pixel format and type matching the storage, for allocating and reading back
+    fn pixel_format(&self) -> (GLenum, GLenum) {
+        match self {
+            ColorFormat::Rgba8 | ColorFormat::Srgb8Alpha8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            ColorFormat::Rgba16F => (gl::RGBA, gl::FLOAT),
+            ColorFormat::R32F => (gl::RED, gl::FLOAT),
+            ColorFormat::R32UI => (gl::RED_INTEGER, gl::UNSIGNED_INT),
+        }
+    }
+
+    /// Integer textures can't be filtered, only read texel by texel
+    pub fn is_integer(&self) -> bool {
+        matches!(self, ColorFormat::R32UI)
+    }
+}
+
+/// Size and attachments of a framebuffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FramebufferSpec {
+    pub width: u32,
+    pub height: u32,
+    /// `None` for a depth-only framebuffer, e.g. for shadow maps
+    pub color_format: Option<ColorFormat>,
+    pub depth: bool,
+    /// Only together with `depth`, they share one attachment
+    pub stencil: bool,
+}
+
+impl FramebufferSpec {
+    /// Color texture of `color_format` with a depth/stencil attachment
+    pub fn new(width: u32, height: u32, color_format: ColorFormat) -> Self {
+        Self {
+            width,
+            height,
+            color_format: Some(color_format),
+            depth: true,
+            stencil: true,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("Framebuffer can't be {}x{}", self.width, self.height));
+        }
+        if self.color_format.is_none() && !self.depth {
+            return Err("Framebuffer needs a color or a depth attachment".to_string());
+        }
+        if self.stencil && !self.depth {
+            return Err("Stencil attachment is only supported together with depth".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Offscreen render target with an optional color texture and depth/stencil texture
+pub struct Framebuffer {
+    id: GLuint,
+    color_texture: GLuint, // 0 when depth-only
+    depth_texture: GLuint, // 0 without depth, holds the stencil too when there is one
+    spec: FramebufferSpec,
+}
+
+impl Framebuffer {
+    /// Framebuffer with a floating-point (RGBA16F) color texture, for HDR rendering
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        Self::with_color_format(width, height, ColorFormat::Rgba16F)
+    }
+
+    /// Framebuffer with a `color_format` color texture and a depth/stencil attachment
+    pub fn with_color_format(
+        width: u32,
+        height: u32,
+        color_format: ColorFormat,
+    ) -> Result<Self, String> {
+        Self::from_spec(&FramebufferSpec::new(width, height, color_format))
+    }
+
+    pub fn from_spec(spec: &FramebufferSpec) -> Result<Self, String> {
+        spec.validate()?;
+        let mut framebuffer = Self {
+            id: 0,
+            color_texture: 0,
+            depth_texture: 0,
+            spec: *spec,
+        };
+        let (width, height) = (spec.width as GLsizei, spec.height as GLsizei);
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+
+            if let Some(color_format) = spec.color_format {
+                let (format, data_type) = color_format.pixel_format();
+                let filter = if color_format.is_integer() { gl::NEAREST } else { gl::LINEAR };
+                framebuffer.color_texture = new_texture(
+                    color_format.as_gl_enum(),
+                    width,
+                    height,
+                    format,
+                    data_type,
+                    filter,
+                );
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    framebuffer.color_texture,
+                    0,
+                );
+            } else {
+                // Nothing to write colors to
+                gl::DrawBuffer(gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            }
+
+            if spec.depth {
+                let (internal_format, format, data_type, attachment) = if spec.stencil {
+                    (
+                        gl::DEPTH24_STENCIL8,
+                        gl::DEPTH_STENCIL,
+                        gl::UNSIGNED_INT_24_8,
+                        gl::DEPTH_STENCIL_ATTACHMENT,
+                    )
+                } else {
+                    (gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT, gl::DEPTH_ATTACHMENT)
+                };
+                framebuffer.depth_texture =
+                    new_texture(internal_format, width, height, format, data_type, gl::NEAREST);
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::TEXTURE_2D,
+                    framebuffer.depth_texture,
+                    0,
+                );
+            }
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!(
+                    "Framebuffer {:?} is incomplete (status 0x{:X})",
+                    spec, status
+                ));
+            }
+        }
+
+        Ok(framebuffer)
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    /// Binds the default (window) framebuffer
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn bind_color_texture(&self, slot: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+        }
+    }
+
+    /// Binds the depth texture, to sample e.g. a shadow map. Nothing is bound without depth.
+    pub fn bind_depth_texture(&self, slot: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_texture);
+        }
+    }
+
+    /// Value of the R32UI color texture at (`x`, `y`), counted from the bottom left
+    pub fn read_uint(&self, x: u32, y: u32) -> Result<u32, String> {
+        if self.spec.color_format != Some(ColorFormat::R32UI) {
+            return Err(format!("Can't read an integer from a {:?} framebuffer", self.spec.color_format));
+        }
+        if x >= self.spec.width || y >= self.spec.height {
+            return Err(format!(
+                "Pixel ({}, {}) is outside of the {}x{} framebuffer",
+                x, y, self.spec.width, self.spec.height
+            ));
+        }
+
+        let (format, data_type) = ColorFormat::R32UI.pixel_format();
+        let mut value: u32 = 0;
+        unsafe {
+            let mut previous = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::ReadPixels(
+                x as GLint,
+                y as GLint,
+                1,
+                1,
+                format,
+                data_type,
+                (&mut value as *mut u32).cast(),
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as GLuint);
+        }
+        Ok(value)
+    }
+
+    pub fn spec(&self) -> &FramebufferSpec {
+        &self.spec
+    }
+
+    pub fn width(&self) -> u32 {
+        self.spec.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.spec.height
+    }
+}
+
+// Texture for an attachment, allocated without data
+unsafe fn new_texture(
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    data_type: GLenum,
+    filter: GLenum,
+) -> GLuint {
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            internal_format as GLint,
+            width,
+            height,
+            0,
+            format,
+            data_type,
+            std::ptr::null(),
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+    }
+    texture
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteTextures(1, &self.color_texture);
+            gl::DeleteTextures(1, &self.depth_texture);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use gl::types::*;
+
+// Queries per section, results are read a few frames later so the CPU never waits on the GPU
+const QUERIES_PER_SECTION: usize = 4;
+
+struct Section {
+    name: &'static str,
+    queries: [GLuint; QUERIES_PER_SECTION],
+    pending: [bool; QUERIES_PER_SECTION],
+    next: usize,
+    last_ms: Option<f32>,
+}
+
+/// Measures how long named sections take on the GPU with `GL_TIME_ELAPSED` queries.
+/// Sections can't be nested and results lag a couple of frames behind.
+pub struct GpuTimer {
+    sections: Vec<Section>, // In the order they were first timed
+    active: Option<usize>,
+}
+
+impl GpuTimer {
+    pub fn new() -> Self {
+        Self {
+            sections: Vec::new(),
+            active: None,
+        }
+    }
+
+    pub fn begin(&mut self, name: &'static str) {
+        if self.active.is_some() {
+            println!("GPU timer section '{}' started while another is active", name);
+            return;
+        }
+
+        let index = match self.sections.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                let mut queries = [0; QUERIES_PER_SECTION];
+                unsafe {
+                    gl::GenQueries(QUERIES_PER_SECTION as GLsizei, queries.as_mut_ptr());
+                }
+                self.sections.push(Section {
+                    name,
+                    queries,
+                    pending: [false; QUERIES_PER_SECTION],
+                    next: 0,
+                    last_ms: None,
+                });
+                self.sections.len() - 1
+            }
+        };
+
+        let section = &mut self.sections[index];
+        // Collect whatever finished before reusing a query
+        section.collect();
+        let slot = section.next;
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, section.queries[slot]);
+        }
+        section.pending[slot] = true;
+        section.next = (slot + 1) % QUERIES_PER_SECTION;
+        self.active = Some(index);
+    }
+
+    pub fn end(&mut self) {
+        if self.active.take().is_some() {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+        }
+    }
+
+    /// Latest available duration of each section in milliseconds
+    pub fn timings(&mut self) -> Vec<(&'static str, f32)> {
+        self.sections
+            .iter_mut()
+            .filter_map(|section| {
+                section.collect();
+                section.last_ms.map(|ms| (section.name, ms))
+            })
+            .collect()
+    }
+}
+
+impl Section {
+    // Reads back every finished query, oldest first, keeping the most recent result
+    fn collect(&mut self) {
+        for offset in 0..QUERIES_PER_SECTION {
+            let slot = (self.next + offset) % QUERIES_PER_SECTION;
+            if !self.pending[slot] {
+                continue;
+            }
+
+            let mut available = 0;
+            unsafe {
+                gl::GetQueryObjectiv(
+                    self.queries[slot],
+                    gl::QUERY_RESULT_AVAILABLE,
+                    &mut available,
+                );
+            }
+            if available == 0 {
+                continue;
+            }
+
+            let mut nanoseconds = 0;
+            unsafe {
+                gl::GetQueryObjectui64v(self.queries[slot], gl::QUERY_RESULT, &mut nanoseconds);
+            }
+            self.pending[slot] = false;
+            self.last_ms = Some(nanoseconds as f32 / 1_000_000.0);
+        }
+    }
+}
+
+impl Default for GpuTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        for section in &self.sections {
+            unsafe {
+                gl::DeleteQueries(QUERIES_PER_SECTION as GLsizei, section.queries.as_ptr());
+            }
+        }
+    }
+}
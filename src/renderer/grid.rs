@@ -0,0 +1,84 @@
+use crate::renderer::mesh::{Mesh, PrimitiveMode, Vertex};
+use crate::renderer::shader::ShaderProgram;
+
+const GRID_COLOR: [f32; 3] = [0.4, 0.4, 0.4];
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.0, 0.0, 1.0],
+    [0.0, 1.0, 0.0, 1.0],
+    [0.0, 0.0, 1.0, 1.0],
+];
+
+/// Reference grid on the XZ plane plus X (red), Y (green) and Z (blue) axes at the origin
+pub struct Grid {
+    shader: ShaderProgram,
+    lines: Mesh,
+    axes: Mesh, // Colored per vertex
+    spacing: f32,
+    extent: f32,
+}
+
+impl Grid {
+    pub fn new(spacing: f32, extent: f32) -> Result<Self, String> {
+        let shader = ShaderProgram::from_files("./shaders/unlit.vs", "./shaders/unlit.fs")?;
+
+        let mut grid = Self {
+            shader,
+            lines: Mesh::new(),
+            axes: Mesh::new(),
+            spacing: 0.0,
+            extent: 0.0,
+        };
+        grid.set_dimensions(spacing, extent);
+        Ok(grid)
+    }
+
+    /// Distance between grid lines and half-size of the grid, rebuilding the meshes if they changed
+    pub fn set_dimensions(&mut self, spacing: f32, extent: f32) {
+        let spacing = spacing.max(0.01);
+        let extent = extent.max(spacing);
+        if spacing == self.spacing && extent == self.extent {
+            return;
+        }
+        self.spacing = spacing;
+        self.extent = extent;
+
+        let line_count = (extent / spacing).floor() as i32;
+        let mut vertices = Vec::new();
+        for i in -line_count..=line_count {
+            let offset = i as f32 * spacing;
+            vertices.push(Vertex::from_position([offset, 0.0, -extent]));
+            vertices.push(Vertex::from_position([offset, 0.0, extent]));
+            vertices.push(Vertex::from_position([-extent, 0.0, offset]));
+            vertices.push(Vertex::from_position([extent, 0.0, offset]));
+        }
+        self.lines = Mesh::new();
+        self.lines.init(&vertices, None);
+        self.lines.set_primitive_mode(PrimitiveMode::Lines);
+
+        let mut vertices = Vec::new();
+        for (i, color) in AXIS_COLORS.iter().enumerate() {
+            let mut end = [0.0; 3];
+            end[i] = extent;
+            vertices.push(Vertex::from_position([0.0; 3]).with_color(*color));
+            vertices.push(Vertex::from_position(end).with_color(*color));
+        }
+        self.axes = Mesh::new();
+        self.axes.init(&vertices, None);
+        self.axes.set_primitive_mode(PrimitiveMode::Lines);
+    }
+
+    pub fn draw(&self) {
+        self.shader.use_program();
+        self.shader.set_uniform_mat4("model", &glam::Mat4::IDENTITY);
+        self.shader.set_uniform_3fv("color", &GRID_COLOR);
+        self.lines.draw();
+
+        // Lift the axes a bit so they don't z-fight with the grid lines
+        self.shader.set_uniform_mat4(
+            "model",
+            &glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.001, 0.0)),
+        );
+        self.shader.set_uniform_3fv("color", &[1.0, 1.0, 1.0]);
+        self.axes.draw();
+    }
+}
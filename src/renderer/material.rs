@@ -1,17 +1,37 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::renderer::shader::ShaderProgram;
+use crate::renderer::shader_variants::{DefineSet, ShaderSource, ShaderVariantCache};
+use crate::renderer::PolygonOffset;
 use crate::renderer::texture::Texture2D;
 
-#[derive(Clone)]
+thread_local! {
+    // Texture units a material can bind to, set from the driver's limit by the renderer
+    static MAX_TEXTURE_SLOTS: Cell<usize> = const { Cell::new(16) };
+}
+
 pub struct Material {
     name: String,
     shader: Rc<ShaderProgram>,
     properties: PropertiesMap,
+    polygon_offset: Option<PolygonOffset>, // Overrides the renderer's global offset
     texture_to_slot: RefCell<HashMap<Rc<Texture2D>, u32>>,
-    texture_slots: RefCell<[bool; 16]>, // Mark which slots are in use
+    texture_slots: RefCell<Vec<bool>>, // Mark which slots are in use, grows up to the slot limit
+    warned_slot_overflow: Cell<bool>,  // Textures past the limit are only reported once
+    skipped_textures: RefCell<Vec<Rc<Texture2D>>>, // Left without a slot, they sample the fallback
+    fallback: RefCell<Option<(Rc<Texture2D>, u32)>>, // Placeholder and its slot, while any is skipped
+    overridden: RefCell<Vec<String>>,  // Properties the last `apply_instance` overrode
+    variants: Option<MaterialVariants>, // Set when the shader is picked from its textures
+}
+
+// Where a material gets its shader variant from, and the defines of the current one
+#[derive(Clone)]
+struct MaterialVariants {
+    cache: Rc<RefCell<ShaderVariantCache>>,
+    source: ShaderSource,
+    defines: DefineSet,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -20,8 +40,11 @@ pub enum MaterialProperty {
     Integer(i32),
     UInteger(u32),
     Float(f32),
-    Vec3([f32; 3]),
-    Color(f32, f32, f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),       // Uploaded as is, for positions, directions and linear colors
+    Color(f32, f32, f32), // sRGB encoded, as picked by hand, converted to linear when uploaded
+    Mat3(glam::Mat3),
+    Mat4(glam::Mat4),
     Texture(Rc<Texture2D>),
 }
 
@@ -31,8 +54,14 @@ impl Material {
             name: name.to_string(),
             shader,
             properties: PropertiesMap::new(),
+            polygon_offset: None,
             texture_to_slot: RefCell::new(HashMap::new()),
-            texture_slots: RefCell::new([false; 16]),
+            texture_slots: RefCell::new(Vec::new()),
+            warned_slot_overflow: Cell::new(false),
+            skipped_textures: RefCell::new(Vec::new()),
+            fallback: RefCell::new(None),
+            overridden: RefCell::new(Vec::new()),
+            variants: None,
         }
     }
 
@@ -45,25 +74,141 @@ impl Material {
             name: name.to_string(),
             shader,
             properties,
+            polygon_offset: None,
             texture_to_slot: RefCell::new(HashMap::new()),
-            texture_slots: RefCell::new([false; 16]),
+            texture_slots: RefCell::new(Vec::new()),
+            warned_slot_overflow: Cell::new(false),
+            skipped_textures: RefCell::new(Vec::new()),
+            fallback: RefCell::new(None),
+            overridden: RefCell::new(Vec::new()),
+            variants: None,
         }
     }
 
+    /// Material whose program is the variant of `source` matching its textures: every texture
+    /// property `name` is compiled in as `#define HAS_<NAME> 1`, e.g. `HAS_MATERIAL_NORMAL` for
+    /// `material.normal`. `set_texture` and `remove_property` keep the variant in sync, call
+    /// `update_variant` after changing textures through `properties_mut`.
+    pub fn with_variants(
+        name: &str,
+        cache: &Rc<RefCell<ShaderVariantCache>>,
+        source: ShaderSource,
+        properties: PropertiesMap,
+    ) -> Result<Self, String> {
+        let defines = properties.texture_defines();
+        let shader = cache.borrow_mut().get(&source, &defines)?;
+        let mut material = Self::new_with_properties(name, shader, properties);
+        material.variants = Some(MaterialVariants {
+            cache: Rc::clone(cache),
+            source,
+            defines,
+        });
+        Ok(material)
+    }
+
+    /// Switches to the shader variant matching the current textures, returns whether it changed.
+    /// Materials not created with `with_variants` keep their shader.
+    pub fn update_variant(&mut self) -> Result<bool, String> {
+        let Some(variants) = &mut self.variants else {
+            return Ok(false);
+        };
+        let defines = self.properties.texture_defines();
+        if defines == variants.defines {
+            return Ok(false);
+        }
+
+        self.shader = variants
+            .cache
+            .borrow_mut()
+            .get(&variants.source, &defines)?;
+        variants.defines = defines;
+        Ok(true)
+    }
+
+    /// Flat-colored material without lighting, for use with the `unlit` shader pair.
+    /// `color` is linear, like light colors.
+    pub fn unlit(shader: Rc<ShaderProgram>, color: [f32; 3]) -> Self {
+        Self::new_with_properties(
+            "unlit",
+            shader,
+            [
+                (
+                    "color".to_string(),
+                    MaterialProperty::Vec3(color),
+                ),
+                ("useTexture".to_string(), MaterialProperty::Boolean(false)),
+            ]
+            .into(),
+        )
+    }
+
+    /// Textured material without lighting, for use with the `unlit` shader pair
+    pub fn unlit_textured(shader: Rc<ShaderProgram>, texture: Rc<Texture2D>) -> Self {
+        Self::new_with_properties(
+            "unlit_textured",
+            shader,
+            [
+                ("color".to_string(), MaterialProperty::Vec3([1.0, 1.0, 1.0])),
+                ("useTexture".to_string(), MaterialProperty::Boolean(true)),
+                ("colorTexture".to_string(), MaterialProperty::Texture(texture)),
+            ]
+            .into(),
+        )
+    }
+
+    /// Copy of the material named `new_name` with `overrides` applied on top of its properties.
+    /// Fails when the overrides change the textures and the matching variant doesn't build.
     pub fn clone_with_overrides(
         &self,
         new_name: &str,
         overrides: HashMap<String, MaterialProperty>,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let mut properties = self.properties.clone();
         properties.map.extend(overrides);
+        let mut material = self.copy_with_properties(new_name, properties);
+        // The overrides may add or remove textures
+        material.update_variant()?;
+        Ok(material)
+    }
+
+    // Same shader and settings as `self`, the variant is only right if the textures didn't change
+    fn copy_with_properties(&self, new_name: &str, properties: PropertiesMap) -> Self {
         Self {
             name: new_name.to_string(),
             shader: Rc::clone(&self.shader),
             properties,
-            texture_to_slot: self.texture_to_slot.clone(),
-            texture_slots: self.texture_slots.clone(),
+            polygon_offset: self.polygon_offset,
+            // Slot assignments are recomputed lazily on the first `bind`
+            texture_to_slot: RefCell::new(HashMap::new()),
+            texture_slots: RefCell::new(Vec::new()),
+            warned_slot_overflow: Cell::new(false),
+            skipped_textures: RefCell::new(Vec::new()),
+            fallback: RefCell::new(None),
+            overridden: RefCell::new(Vec::new()),
+            variants: self.variants.clone(),
+        }
+    }
+
+    /// Sets the texture property `name` and switches to the shader variant with it
+    pub fn set_texture(&mut self, name: &str, texture: Rc<Texture2D>) -> Result<(), String> {
+        self.properties.set_texture(name, texture);
+        self.update_variant().map(|_| ())
+    }
+
+    /// Removes the property `name`, switching to the variant without it if it was a texture
+    pub fn remove_property(&mut self, name: &str) -> Result<(), String> {
+        self.properties.delete(name);
+        // A texture that didn't fit before may have a slot now
+        if !self.skipped_textures.borrow().is_empty() {
+            self.update_texture_slots();
         }
+        self.update_variant().map(|_| ())
+    }
+
+    /// Texture units available to each material, the renderer sets it to
+    /// `GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS`. Textures past it show a placeholder.
+    pub fn set_max_texture_slots(count: usize) {
+        MAX_TEXTURE_SLOTS.set(count);
     }
 
     pub fn set_name(&mut self, name: &str) {
@@ -86,97 +231,266 @@ impl Material {
         &mut self.properties
     }
 
-    pub fn use_material(&self, overrides: &PropertiesMap) {
+    pub fn polygon_offset(&self) -> Option<PolygonOffset> {
+        self.polygon_offset
+    }
+
+    /// Depth offset for objects using this material, e.g. to push thin geometry back
+    pub fn set_polygon_offset(&mut self, offset: Option<PolygonOffset>) {
+        self.polygon_offset = offset;
+    }
+
+    /// Checks every property against the shader's active uniforms and returns the names
+    /// that don't match any, e.g. typos or uniforms optimized out by the compiler
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut invalid: Vec<String> = self
+            .properties
+            .map
+            .keys()
+            .filter(|name| !self.shader.contains_uniform(name))
+            .cloned()
+            .collect();
+        if invalid.is_empty() {
+            return Ok(());
+        }
+
+        invalid.sort();
+        Err(invalid)
+    }
+
+    /// Makes the material current: uses its program, uploads its properties and binds its
+    /// textures. Objects sharing the material can then be drawn one after another, each after
+    /// `apply_instance`, without binding it again.
+    pub fn bind(&self) {
         self.shader.use_program();
 
         for (name, value) in &self.properties.map {
-            let value = match overrides.map.get(name) {
-                Some(value) => value,
-                None => value,
-            };
-
             match value {
-                MaterialProperty::Boolean(value) => {
-                    self.shader.set_uniform_1i(name, *value as i32);
-                }
-                MaterialProperty::Integer(value) => {
-                    self.shader.set_uniform_1i(name, *value);
-                }
-                MaterialProperty::UInteger(value) => {
-                    self.shader.set_uniform_1ui(name, *value);
-                }
-                MaterialProperty::Float(value) => {
-                    self.shader.set_uniform_1f(name, *value);
-                }
-                MaterialProperty::Vec3(value) => {
-                    self.shader.set_uniform_3fv(name, value);
-                }
-                MaterialProperty::Color(r, g, b) => {
-                    self.shader.set_uniform_3f(name, *r, *g, *b);
-                }
                 MaterialProperty::Texture(texture) => {
-                    let slot = self.texture_to_slot.borrow().get(texture).copied();
-                    let texture_slot = match slot {
-                        Some(slot) => slot,
-                        None => {
-                            // Updates for all textures
-                            self.update_texture_slots();
-                            self.texture_to_slot.borrow()[texture] // panic if not found
-                        }
-                    };
-                    self.shader.set_uniform_1i(name, texture_slot as i32);
+                    let mut slot = self.sampler_slot(texture);
+                    if slot.is_none() {
+                        // Updates for all textures
+                        self.update_texture_slots();
+                        slot = self.sampler_slot(texture);
+                    }
+                    if let Some(slot) = slot {
+                        self.shader.set_uniform_1i(name, slot as i32);
+                    }
                 }
+                value => self.set_uniform(name, value),
             }
         }
+        // Every property has its own value again
+        self.overridden.borrow_mut().clear();
 
-        // Bind textures
-        for (texture, slot) in &*self.texture_to_slot.borrow() {
+        // Bind textures, in slot order so the same calls are made every time
+        let texture_to_slot = self.texture_to_slot.borrow();
+        let mut bindings: Vec<_> = texture_to_slot.iter().collect();
+        bindings.sort_by_key(|(_, slot)| **slot);
+        for (texture, slot) in bindings {
             texture.bind_slot(*slot);
         }
+        if let Some((fallback, slot)) = self.fallback.borrow().as_ref() {
+            fallback.bind_slot(*slot);
+        }
+    }
+
+    /// Sets up the next object drawn with the bound material: its `model` and `normal_matrix`
+    /// uniforms and its overrides. Properties overridden by the previous object but not by
+    /// this one go back to the material's values. Only properties of the material can be
+    /// overridden, a texture override takes the slot of the texture it replaces.
+    pub fn apply_instance(&self, overrides: &PropertiesMap, model: &glam::Mat4) {
+        let mut overridden = self.overridden.borrow_mut();
+        for name in overridden.iter() {
+            if !overrides.map.contains_key(name)
+                && let Some(value) = self.properties.map.get(name)
+            {
+                self.set_instance_value(name, value, value);
+            }
+        }
+        overridden.clear();
+
+        for (name, value) in &overrides.map {
+            if let Some(own_value) = self.properties.map.get(name) {
+                self.set_instance_value(name, own_value, value);
+                overridden.push(name.clone());
+            }
+        }
+
+        // The model matrix is per object, it is always re-set rather than compared against the cache
+        self.shader.invalidate_uniform("model");
+        self.shader.set_uniform_mat4("model", model);
+        if self.shader.contains_uniform("normal_matrix") {
+            let normal_matrix = glam::Mat3::from_mat4(*model).inverse().transpose();
+            self.shader.invalidate_uniform("normal_matrix");
+            self.shader.set_uniform_mat3("normal_matrix", &normal_matrix);
+        }
+    }
+
+    // Uploads `value` for the property `name`, whose value in the material is `own_value`
+    fn set_instance_value(&self, name: &str, own_value: &MaterialProperty, value: &MaterialProperty) {
+        match (own_value, value) {
+            (MaterialProperty::Texture(own), MaterialProperty::Texture(texture)) => {
+                if let Some(slot) = self.texture_slot(own) {
+                    texture.bind_slot(slot);
+                }
+            }
+            (MaterialProperty::Texture(_), _) => {
+                println!("Override of texture '{}' in material '{}' isn't a texture", name, self.name);
+            }
+            _ => {
+                // Overrides differ between objects sharing this shader, always upload them
+                self.shader.invalidate_uniform(name);
+                self.set_uniform(name, value);
+            }
+        }
+    }
+
+    // Any property but textures, which need a slot
+    fn set_uniform(&self, name: &str, value: &MaterialProperty) {
+        match value {
+            MaterialProperty::Boolean(value) => {
+                self.shader.set_uniform_1i(name, *value as i32);
+            }
+            MaterialProperty::Integer(value) => {
+                self.shader.set_uniform_1i(name, *value);
+            }
+            MaterialProperty::UInteger(value) => {
+                self.shader.set_uniform_1ui(name, *value);
+            }
+            MaterialProperty::Float(value) => {
+                self.shader.set_uniform_1f(name, *value);
+            }
+            MaterialProperty::Vec2([x, y]) => {
+                self.shader.set_uniform_2f(name, *x, *y);
+            }
+            MaterialProperty::Vec3(value) => {
+                self.shader.set_uniform_3fv(name, value);
+            }
+            MaterialProperty::Color(r, g, b) => {
+                let [r, g, b] = [*r, *g, *b].map(srgb_to_linear);
+                self.shader.set_uniform_3f(name, r, g, b);
+            }
+            MaterialProperty::Mat3(value) => {
+                self.shader.set_uniform_mat3(name, value);
+            }
+            MaterialProperty::Mat4(value) => {
+                self.shader.set_uniform_mat4(name, value);
+            }
+            MaterialProperty::Texture(_) => {}
+        }
     }
 
+    /// Texture unit `texture` is bound to by `bind`, `None` until the first bind or when there
+    /// was no unit left for it
+    pub fn texture_slot(&self, texture: &Rc<Texture2D>) -> Option<u32> {
+        self.texture_to_slot.borrow().get(texture).copied()
+    }
+
+    // Unit the samplers of `texture` read from, the fallback's for skipped textures
+    fn sampler_slot(&self, texture: &Rc<Texture2D>) -> Option<u32> {
+        if let Some(slot) = self.texture_slot(texture) {
+            return Some(slot);
+        }
+        if self.skipped_textures.borrow().contains(texture) {
+            return self.fallback.borrow().as_ref().map(|(_, slot)| *slot);
+        }
+        None
+    }
+
+    // Texture2D hashes by its GL id only, the reload bookkeeping inside doesn't affect the key
+    #[allow(clippy::mutable_key_type)]
     fn update_texture_slots(&self) {
-        let used_textures: HashSet<_> = self
+        // In property name order, so the same material always gets the same slots
+        let mut texture_properties: Vec<_> = self
             .properties
             .map
-            .values()
-            .filter_map(|value| {
+            .iter()
+            .filter_map(|(name, value)| {
                 if let MaterialProperty::Texture(texture) = value {
-                    Some(Rc::clone(texture))
+                    Some((name, texture))
                 } else {
                     None
                 }
             })
             .collect();
-        let bound_textures: HashSet<_> = self
-            .texture_to_slot
-            .borrow()
-            .keys()
-            .map(Rc::clone)
-            .collect();
+        texture_properties.sort_by_key(|(name, _)| *name);
+        let mut used_textures: Vec<&Rc<Texture2D>> = Vec::new();
+        for (_, texture) in texture_properties {
+            if !used_textures.contains(&texture) {
+                used_textures.push(texture);
+            }
+        }
+        // Both maps are borrowed once for the whole update instead of per access
+        let mut texture_slots = self.texture_slots.borrow_mut();
+        let mut texture_to_slot = self.texture_to_slot.borrow_mut();
+
+        // When not every texture fits, the last slot is kept for the fallback
+        let max_slots = MAX_TEXTURE_SLOTS.get();
+        let available = if used_textures.len() > max_slots {
+            max_slots.saturating_sub(1)
+        } else {
+            max_slots
+        };
+
+        // Free the slots of unused textures and the fallback's, the others keep theirs
+        texture_to_slot.retain(|texture, slot| {
+            let keep = used_textures.contains(&texture) && (*slot as usize) < available;
+            if !keep {
+                texture_slots[*slot as usize] = false;
+            }
+            keep
+        });
+        texture_slots.truncate(available);
+
+        // Add new textures to the map, each in the lowest free slot. The ones left over are
+        // the last in property name order.
+        let mut skipped = Vec::new();
+        for texture in used_textures {
+            if texture_to_slot.contains_key(texture) {
+                continue;
+            }
+            let slot = match texture_slots.iter().position(|&x| !x) {
+                Some(slot) => slot,
+                None if texture_slots.len() < available => {
+                    texture_slots.push(false);
+                    texture_slots.len() - 1
+                }
+                None => {
+                    skipped.push(Rc::clone(texture));
+                    continue;
+                }
+            };
+            texture_slots[slot] = true;
+            texture_to_slot.insert(Rc::clone(texture), slot as u32);
+        }
 
-        // Remove unused textures from the map
-        let unused_textures: HashSet<_> = bound_textures.difference(&used_textures).collect();
-        for texture in unused_textures {
-            let slot = self.texture_to_slot.borrow()[texture];
-            self.texture_slots.borrow_mut()[slot as usize] = false;
-            self.texture_to_slot.borrow_mut().remove(texture);
-        }
-
-        // Add new textures to the map
-        let unbound_textures: HashSet<_> = used_textures.difference(&bound_textures).collect();
-        for texture in unbound_textures {
-            let slot = self
-                .texture_slots
-                .borrow()
-                .iter()
-                .position(|&x| !x)
-                .unwrap();
-            self.texture_slots.borrow_mut()[slot] = true;
-            self.texture_to_slot
-                .borrow_mut()
-                .insert(Rc::clone(texture), slot as u32);
+        // Kept across updates, so the placeholder is only created once
+        let mut fallback = self.fallback.borrow_mut();
+        if skipped.is_empty() {
+            *fallback = None;
+        } else {
+            let texture = match fallback.take() {
+                Some((texture, _)) => texture,
+                None => Rc::new(Texture2D::default_checkerboard()),
+            };
+            *fallback = Some((texture, available as u32));
+            if !self.warned_slot_overflow.replace(true) {
+                println!(
+                    "Material '{}' uses more textures than the {} texture slots, {} will show a placeholder",
+                    self.name,
+                    max_slots,
+                    skipped.len()
+                );
+            }
         }
+        *self.skipped_textures.borrow_mut() = skipped;
+    }
+}
+
+impl Clone for Material {
+    fn clone(&self) -> Self {
+        self.copy_with_properties(&self.name, self.properties.clone())
     }
 }
 
@@ -197,6 +511,27 @@ impl PropertiesMap {
         self.map.insert(name.to_string(), value);
     }
 
+    // `HAS_<NAME>` for every texture, the name uppercased with non-alphanumerics as underscores
+    fn texture_defines(&self) -> DefineSet {
+        self.map
+            .iter()
+            .filter(|(_, value)| matches!(value, MaterialProperty::Texture(_)))
+            .map(|(name, _)| {
+                let name: String = name
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() {
+                            c.to_ascii_uppercase()
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                format!("HAS_{}", name)
+            })
+            .collect()
+    }
+
     pub fn set_boolean(&mut self, name: &str, value: bool) {
         self.set(name, MaterialProperty::Boolean(value));
     }
@@ -213,14 +548,27 @@ impl PropertiesMap {
         self.set(name, MaterialProperty::Float(value));
     }
 
+    pub fn set_vec2(&mut self, name: &str, value: [f32; 2]) {
+        self.set(name, MaterialProperty::Vec2(value));
+    }
+
     pub fn set_vec3(&mut self, name: &str, value: [f32; 3]) {
         self.set(name, MaterialProperty::Vec3(value));
     }
 
+    /// sRGB encoded color, use `set_vec3` for colors that are already linear
     pub fn set_color(&mut self, name: &str, r: f32, g: f32, b: f32) {
         self.set(name, MaterialProperty::Color(r, g, b));
     }
 
+    pub fn set_mat3(&mut self, name: &str, value: glam::Mat3) {
+        self.set(name, MaterialProperty::Mat3(value));
+    }
+
+    pub fn set_mat4(&mut self, name: &str, value: glam::Mat4) {
+        self.set(name, MaterialProperty::Mat4(value));
+    }
+
     pub fn set_texture(&mut self, name: &str, texture: Rc<Texture2D>) {
         self.set(name, MaterialProperty::Texture(texture));
     }
@@ -228,6 +576,26 @@ impl PropertiesMap {
     pub fn delete(&mut self, name: &str) {
         self.map.remove(name);
     }
+
+    pub fn get(&self, name: &str) -> Option<&MaterialProperty> {
+        self.map.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut MaterialProperty> {
+        self.map.get_mut(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
 }
 
 impl From<HashMap<String, MaterialProperty>> for PropertiesMap {
@@ -241,3 +609,249 @@ impl<const N: usize> From<[(String, MaterialProperty); N]> for PropertiesMap {
         Self { map: array.into() }
     }
 }
+
+/// Decodes an sRGB encoded channel in [0, 1] to linear
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear channel in [0, 1] to sRGB
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::texture::TextureConfig;
+    use crate::test_support;
+
+    fn white_texture() -> Rc<Texture2D> {
+        Rc::new(Texture2D::new_from_pixels(1, 1, &[255; 3], &TextureConfig::default()).unwrap())
+    }
+
+    fn phong_source() -> ShaderSource {
+        ShaderSource::new("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+    }
+
+    #[test]
+    fn phong_shader_builds_with_any_set_of_maps() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let mut cache = ShaderVariantCache::new();
+        for names in [
+            &[][..],
+            &["HAS_MATERIAL_DIFFUSE"][..],
+            &["HAS_MATERIAL_SPECULAR"][..],
+            &["HAS_MATERIAL_DIFFUSE", "HAS_MATERIAL_SPECULAR"][..],
+        ] {
+            let defines: DefineSet = names.iter().map(|name| name.to_string()).collect();
+            cache.get(&phong_source(), &defines).unwrap();
+        }
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn texture_setters_switch_the_variant() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let cache = Rc::new(RefCell::new(ShaderVariantCache::new()));
+        let mut properties = PropertiesMap::new();
+        properties.set_texture("material.diffuse", white_texture());
+        let mut material =
+            Material::with_variants("phong", &cache, phong_source(), properties).unwrap();
+        let diffuse_only = material.shader();
+
+        material.set_texture("material.specular", white_texture()).unwrap();
+        assert!(!Rc::ptr_eq(&material.shader(), &diffuse_only));
+
+        material.remove_property("material.specular").unwrap();
+        assert!(Rc::ptr_eq(&material.shader(), &diffuse_only));
+
+        let overrides = [(
+            "material.specular".to_string(),
+            MaterialProperty::Texture(white_texture()),
+        )];
+        let copy = material.clone_with_overrides("copy", overrides.into()).unwrap();
+        assert!(!Rc::ptr_eq(&copy.shader(), &diffuse_only));
+        assert_eq!(cache.borrow().len(), 2);
+    }
+
+    // GL id of the texture bound to unit `slot`
+    fn bound_texture(slot: u32) -> u32 {
+        let mut id = 0;
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut id);
+        }
+        id as u32
+    }
+
+    // Texture unit the sampler uniform `name` of `shader` reads from
+    fn sampler_unit(shader: &ShaderProgram, name: &str) -> u32 {
+        let mut unit = -1;
+        unsafe {
+            let name = std::ffi::CString::new(name).unwrap();
+            let location = gl::GetUniformLocation(shader.id(), name.as_ptr());
+            gl::GetUniformiv(shader.id(), location, &mut unit);
+        }
+        unit as u32
+    }
+
+    #[test]
+    fn clone_binds_its_own_textures() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let shader = Rc::new(
+            ShaderProgram::from_files("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+                .unwrap(),
+        );
+        let (diffuse, specular, other_diffuse) = (white_texture(), white_texture(), white_texture());
+        let mut properties = PropertiesMap::new();
+        properties.set_texture("material.diffuse", Rc::clone(&diffuse));
+        properties.set_texture("material.specular", Rc::clone(&specular));
+        let material = Material::new_with_properties("phong", shader, properties);
+        // The original has its slots assigned before it's cloned
+        material.bind();
+
+        let overrides = [(
+            "material.diffuse".to_string(),
+            MaterialProperty::Texture(Rc::clone(&other_diffuse)),
+        )];
+        let copy = material.clone_with_overrides("copy", overrides.into()).unwrap();
+        assert_eq!(copy.texture_slot(&other_diffuse), None);
+        copy.bind();
+
+        assert_eq!(copy.texture_slot(&diffuse), None);
+        for (name, texture) in [
+            ("material.diffuse", &other_diffuse),
+            ("material.specular", &specular),
+        ] {
+            let slot = copy.texture_slot(texture).unwrap();
+            assert_eq!(sampler_unit(&copy.shader(), name), slot);
+            assert_eq!(bound_texture(slot), texture.id());
+        }
+    }
+
+    // Samples the textures `a`, `b` and `c`
+    fn three_sampler_shader() -> Rc<ShaderProgram> {
+        let shader = ShaderProgram::from_sources(
+            "#version 450 core\nvoid main() { gl_Position = vec4(0.0); }",
+            "#version 450 core
+            uniform sampler2D a, b, c;
+            out vec4 color;
+            void main() {
+                color = texture(a, vec2(0.0)) + texture(b, vec2(0.0)) + texture(c, vec2(0.0));
+            }",
+        );
+        Rc::new(shader.unwrap())
+    }
+
+    #[test]
+    fn texture_slots_are_stable_across_binds() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let textures: Vec<_> = (0..3).map(|_| white_texture()).collect();
+        let mut properties = PropertiesMap::new();
+        for (name, texture) in ["c", "a", "b"].into_iter().zip(&textures) {
+            properties.set_texture(name, Rc::clone(texture));
+        }
+        let mut material = Material::new_with_properties("slots", three_sampler_shader(), properties);
+        let slots = |material: &Material| -> Vec<_> {
+            textures.iter().map(|texture| material.texture_slot(texture)).collect()
+        };
+
+        // Assigned in property name order
+        material.bind();
+        let first = slots(&material);
+        assert_eq!(first, [Some(2), Some(0), Some(1)]);
+        for _ in 0..10 {
+            material.bind();
+            assert_eq!(slots(&material), first);
+        }
+
+        // A replaced texture takes the freed slot, the others stay where they are
+        let replacement = white_texture();
+        material.properties_mut().set_texture("a", Rc::clone(&replacement));
+        material.bind();
+        assert_eq!(slots(&material), [Some(2), None, Some(1)]);
+        assert_eq!(material.texture_slot(&replacement), Some(0));
+    }
+
+    #[test]
+    fn textures_past_the_slot_limit_sample_the_fallback() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        Material::set_max_texture_slots(2);
+        let textures: Vec<_> = (0..3).map(|_| white_texture()).collect();
+        let mut properties = PropertiesMap::new();
+        for (name, texture) in ["a", "b", "c"].into_iter().zip(&textures) {
+            properties.set_texture(name, Rc::clone(texture));
+        }
+        let mut material =
+            Material::new_with_properties("overflow", three_sampler_shader(), properties);
+
+        // Only `a` fits, the last slot goes to the placeholder for the others
+        material.bind();
+        let fallback = bound_texture(1);
+        for _ in 0..3 {
+            material.bind();
+            let shader = material.shader();
+            assert_eq!(material.texture_slot(&textures[0]), Some(0));
+            assert_eq!(bound_texture(0), textures[0].id());
+            for name in ["b", "c"] {
+                assert_eq!(sampler_unit(&shader, name), 1);
+            }
+            assert_eq!(bound_texture(1), fallback);
+            assert!(textures.iter().all(|texture| texture.id() != fallback));
+        }
+
+        // With one texture less, all of them fit again
+        material.remove_property("c").unwrap();
+        material.bind();
+        assert_eq!(material.texture_slot(&textures[1]), Some(1));
+        assert_eq!(bound_texture(1), textures[1].id());
+        Material::set_max_texture_slots(16);
+    }
+
+    #[test]
+    fn model_is_uploaded_for_every_instance() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let material = Material::unlit(test_support::unlit_shader(), [1.0; 3]);
+        let shader = material.shader();
+        let model = glam::Mat4::from_translation(glam::vec3(1.0, 2.0, 3.0));
+        material.bind();
+        material.apply_instance(&PropertiesMap::new(), &model);
+
+        // Changed behind the cache's back, e.g. by another pass drawing with this program
+        let location = unsafe {
+            let name = std::ffi::CString::new("model").unwrap();
+            gl::GetUniformLocation(shader.id(), name.as_ptr())
+        };
+        unsafe {
+            gl::UniformMatrix4fv(location, 1, gl::FALSE, glam::Mat4::IDENTITY.as_ref().as_ptr());
+        }
+
+        material.apply_instance(&PropertiesMap::new(), &model);
+        let mut uploaded = [0.0; 16];
+        unsafe {
+            gl::GetUniformfv(shader.id(), location, uploaded.as_mut_ptr());
+        }
+        assert_eq!(glam::Mat4::from_cols_array(&uploaded), model);
+    }
+}
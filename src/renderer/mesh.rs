@@ -1,19 +1,125 @@
-use crate::renderer::buffer::{Buffer, BufferType};
+use crate::renderer::buffer::{Buffer, BufferType, BufferUsage};
+use crate::renderer::shader::ShaderProgram;
 
 use gl::types::*;
 
+/// Vertex layout shared by every mesh. The fields are positional to keep the `#[repr(C)]`
+/// layout obvious, build vertices with `new` or `from_position` and the `with_*` methods
+/// rather than the tuple constructor, where normals and UVs are easily swapped.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C)]
 pub struct Vertex(
     pub [f32; 3], // position
     pub [f32; 3], // normal vectors
     pub [f32; 2], // texture coordinates
+    pub [f32; 4], // color, multiplied into the result by shaders that use it
+    pub [f32; 2], // second texture coordinates, e.g. for lightmaps
+    pub [f32; 4], // tangent along +U, w is the sign of the bitangent
 );
 
+impl Vertex {
+    /// Vertex with a white color, which leaves the shading unchanged. The second set of
+    /// texture coordinates is a copy of the first and the tangent points along +X.
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Self(position, normal, uv, [1.0; 4], uv, [1.0, 0.0, 0.0, 1.0])
+    }
+
+    /// Vertex with only a position, the normal points up and the texture coordinates are zero
+    pub fn from_position(position: [f32; 3]) -> Self {
+        Self::new(position, [0.0, 1.0, 0.0], [0.0, 0.0])
+    }
+
+    pub fn with_normal(mut self, normal: [f32; 3]) -> Self {
+        self.1 = normal;
+        self
+    }
+
+    /// Sets both sets of texture coordinates, use `with_uv1` afterwards for a different second set
+    pub fn with_uv(mut self, uv: [f32; 2]) -> Self {
+        self.2 = uv;
+        self.4 = uv;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.3 = color;
+        self
+    }
+
+    pub fn with_uv1(mut self, uv1: [f32; 2]) -> Self {
+        self.4 = uv1;
+        self
+    }
+
+    pub fn with_tangent(mut self, tangent: [f32; 4]) -> Self {
+        self.5 = tangent;
+        self
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.0
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        self.1
+    }
+
+    pub fn uv(&self) -> [f32; 2] {
+        self.2
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.3
+    }
+
+    pub fn uv1(&self) -> [f32; 2] {
+        self.4
+    }
+
+    pub fn tangent(&self) -> [f32; 4] {
+        self.5
+    }
+}
+
+// Name of each `Vertex` field in the shaders, with its default location, component count and offset
+const VERTEX_ATTRIBUTES: [(&str, GLuint, GLint, usize); 6] = [
+    ("aPos", 0, 3, std::mem::offset_of!(Vertex, 0)),
+    ("aNormal", 1, 3, std::mem::offset_of!(Vertex, 1)),
+    ("aTexCoord", 2, 2, std::mem::offset_of!(Vertex, 2)),
+    ("aColor", 3, 4, std::mem::offset_of!(Vertex, 3)),
+    ("aTexCoord1", 4, 2, std::mem::offset_of!(Vertex, 4)),
+    ("aTangent", 5, 4, std::mem::offset_of!(Vertex, 5)),
+];
+
+/// Primitive topology used when drawing a mesh
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PrimitiveMode {
+    #[default]
+    Triangles,
+    TriangleStrip,
+    Lines,
+    LineStrip,
+    Points,
+}
+
+impl PrimitiveMode {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            PrimitiveMode::Triangles => gl::TRIANGLES,
+            PrimitiveMode::TriangleStrip => gl::TRIANGLE_STRIP,
+            PrimitiveMode::Lines => gl::LINES,
+            PrimitiveMode::LineStrip => gl::LINE_STRIP,
+            PrimitiveMode::Points => gl::POINTS,
+        }
+    }
+}
+
 pub struct Mesh {
     vbo: Buffer,
     ebo: Option<Buffer>,
     vao: GLuint,
     number_of_drawables: GLsizei,
+    primitive_mode: PrimitiveMode,
 }
 
 impl Mesh {
@@ -23,6 +129,7 @@ impl Mesh {
             ebo: None,
             vao: 0,
             number_of_drawables: 0,
+            primitive_mode: PrimitiveMode::default(),
         };
         unsafe {
             gl::GenVertexArrays(1, &mut mesh.vao);
@@ -31,11 +138,42 @@ impl Mesh {
     }
 
     pub fn init(&mut self, vertices: &[Vertex], indices: Option<&[u32]>) {
+        self.init_with_usage(vertices, indices, BufferUsage::Static);
+    }
+
+    /// Same as `init`, but with a usage hint for meshes whose vertices are updated later
+    pub fn init_with_usage(
+        &mut self,
+        vertices: &[Vertex],
+        indices: Option<&[u32]>,
+        usage: BufferUsage,
+    ) {
+        self.init_impl(vertices, indices, usage, None);
+    }
+
+    /// Same as `init`, but looks up the attribute locations by name in `program` instead of
+    /// assuming the default ones. Attributes the program doesn't use are left disabled.
+    pub fn init_for_program(
+        &mut self,
+        vertices: &[Vertex],
+        indices: Option<&[u32]>,
+        program: &ShaderProgram,
+    ) {
+        self.init_impl(vertices, indices, BufferUsage::Static, Some(program));
+    }
+
+    fn init_impl(
+        &mut self,
+        vertices: &[Vertex],
+        indices: Option<&[u32]>,
+        usage: BufferUsage,
+        program: Option<&ShaderProgram>,
+    ) {
         unsafe {
             gl::BindVertexArray(self.vao);
         }
 
-        self.vbo.upload_data(vertices);
+        self.vbo.upload_data_with_usage(vertices, usage);
 
         // If indices are not provided, then the number of drawables is the number of vertices
         self.number_of_drawables = vertices.len() as GLsizei;
@@ -46,36 +184,26 @@ impl Mesh {
             self.number_of_drawables = indices.len() as GLsizei;
         }
 
-        unsafe {
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                std::mem::size_of::<Vertex>() as GLsizei,
-                std::ptr::null(),
-            );
-            gl::EnableVertexAttribArray(0);
-
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                std::mem::size_of::<Vertex>() as GLsizei,
-                std::mem::offset_of!(Vertex, 1) as *const _,
-            );
-            gl::EnableVertexAttribArray(1);
-
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                std::mem::size_of::<Vertex>() as GLsizei,
-                std::mem::offset_of!(Vertex, 2) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
+        for (name, default_location, components, offset) in VERTEX_ATTRIBUTES {
+            let location = match program {
+                Some(program) => match program.attribute_location(name) {
+                    Some(location) => location,
+                    None => continue,
+                },
+                None => default_location,
+            };
+
+            unsafe {
+                gl::VertexAttribPointer(
+                    location,
+                    components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    std::mem::size_of::<Vertex>() as GLsizei,
+                    offset as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+            }
         }
 
         unsafe {
@@ -83,25 +211,128 @@ impl Mesh {
         }
     }
 
+    /// Overwrites the vertex data in place. The new data can't be larger than what was
+    /// passed to `init`; for non-indexed meshes the draw count follows `vertices`.
+    pub fn update_vertices(&mut self, vertices: &[Vertex]) -> Result<(), String> {
+        self.vbo.update_data(0, vertices)?;
+        self.vbo.unbind();
+
+        if self.ebo.is_none() {
+            self.number_of_drawables = vertices.len() as GLsizei;
+        }
+        Ok(())
+    }
+
+    pub fn set_primitive_mode(&mut self, mode: PrimitiveMode) {
+        self.primitive_mode = mode;
+    }
+
+    pub fn primitive_mode(&self) -> PrimitiveMode {
+        self.primitive_mode
+    }
+
+    /// Number of triangles a draw produces, zero for lines and points
+    pub fn triangle_count(&self) -> u32 {
+        let count = self.number_of_drawables as u32;
+        match self.primitive_mode {
+            PrimitiveMode::Triangles => count / 3,
+            PrimitiveMode::TriangleStrip => count.saturating_sub(2),
+            PrimitiveMode::Lines | PrimitiveMode::LineStrip | PrimitiveMode::Points => 0,
+        }
+    }
+
     pub fn draw(&self) {
+        let mode = self.primitive_mode.as_gl_enum();
         unsafe {
             gl::BindVertexArray(self.vao);
             if self.ebo.is_some() {
                 gl::DrawElements(
-                    gl::TRIANGLES,
+                    mode,
                     self.number_of_drawables,
                     gl::UNSIGNED_INT,
                     std::ptr::null(),
                 );
             } else {
-                gl::DrawArrays(gl::TRIANGLES, 0, self.number_of_drawables);
+                gl::DrawArrays(mode, 0, self.number_of_drawables);
             }
         }
     }
 }
 
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        // The vertex and index buffers delete themselves
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
 impl Default for Mesh {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::headless::Headless;
+    use crate::renderer::material::Material;
+    use crate::scene::Scene;
+    use crate::test_support;
+
+    // Quad covering x in [left, left + 0.5] at z = -1
+    fn strip(left: f32) -> [Vertex; 4] {
+        let normal = [0.0, 0.0, 1.0];
+        [
+            Vertex::new([left, -0.5, -1.0], normal, [0.0, 0.0]),
+            Vertex::new([left + 0.5, -0.5, -1.0], normal, [1.0, 0.0]),
+            Vertex::new([left + 0.5, 0.5, -1.0], normal, [1.0, 1.0]),
+            Vertex::new([left, 0.5, -1.0], normal, [0.0, 1.0]),
+        ]
+    }
+
+    // Whether the left and right side of the image have anything drawn
+    fn render(mesh: &Rc<Mesh>, headless: &mut Headless) -> (bool, bool) {
+        let mut scene = Scene::new();
+        scene.register_mesh("strip", Rc::clone(mesh));
+        let material = Material::unlit(test_support::unlit_shader(), [1.0, 1.0, 1.0]);
+        scene.register_material("white", Rc::new(RefCell::new(material)));
+        scene.spawn("strip", "white").unwrap();
+        let image = headless
+            .render_to_image(&scene, &test_support::camera(32, 32), 32, 32)
+            .unwrap();
+        let lit = |x| image.get_pixel(x, 16).0 != [0, 0, 0];
+        (lit(8), lit(24))
+    }
+
+    #[test]
+    fn updated_vertices_are_drawn() {
+        let Some(mut headless) = test_support::context() else {
+            return;
+        };
+        let mut mesh = Mesh::new();
+        mesh.init_with_usage(&strip(-0.5), Some(&[0, 1, 2, 2, 3, 0]), BufferUsage::Dynamic);
+        let mut mesh = Rc::new(mesh);
+        assert_eq!(render(&mesh, &mut headless), (true, false));
+
+        Rc::get_mut(&mut mesh).unwrap().update_vertices(&strip(0.0)).unwrap();
+        assert_eq!(render(&mesh, &mut headless), (false, true));
+    }
+
+    #[test]
+    fn update_larger_than_the_buffer_fails() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let mut mesh = Mesh::new();
+        mesh.init_with_usage(&strip(0.0)[..3], None, BufferUsage::Dynamic);
+        assert!(mesh.update_vertices(&strip(0.0)).is_err());
+        assert!(mesh.update_vertices(&strip(0.0)[..2]).is_ok());
+        assert_eq!(mesh.triangle_count(), 0);
+    }
+}
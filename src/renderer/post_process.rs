@@ -0,0 +1,176 @@
+use gl::types::*;
+
+use crate::renderer::framebuffer::{ColorFormat, Framebuffer, FramebufferSpec};
+use crate::renderer::shader::ShaderProgram;
+use crate::renderer::Rect;
+
+/// Operator used to bring HDR colors into the displayable range
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapping {
+    #[default]
+    Reinhard,
+    Aces,
+}
+
+impl ToneMapping {
+    fn as_uniform(&self) -> i32 {
+        match self {
+            ToneMapping::Reinhard => 0,
+            ToneMapping::Aces => 1,
+        }
+    }
+}
+
+// Number of horizontal + vertical blur pairs applied to the bright pixels
+const BLOOM_BLUR_PASSES: usize = 5;
+
+/// Fullscreen passes applied to the offscreen scene render
+pub struct PostProcess {
+    tonemap_shader: ShaderProgram,
+    bloom_extract_shader: ShaderProgram,
+    blur_shader: ShaderProgram,
+    bloom_framebuffers: Option<[Framebuffer; 2]>, // Half resolution ping-pong targets
+    vao: GLuint, // Empty, the fullscreen triangle is generated in the vertex shader
+}
+
+impl PostProcess {
+    pub fn new() -> Result<Self, String> {
+        let tonemap_shader =
+            ShaderProgram::from_files("./shaders/fullscreen.vs", "./shaders/tonemap.fs")?;
+        let bloom_extract_shader =
+            ShaderProgram::from_files("./shaders/fullscreen.vs", "./shaders/bloom_extract.fs")?;
+        let blur_shader = ShaderProgram::from_files("./shaders/fullscreen.vs", "./shaders/blur.fs")?;
+
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        Ok(Self {
+            tonemap_shader,
+            bloom_extract_shader,
+            blur_shader,
+            bloom_framebuffers: None,
+            vao,
+        })
+    }
+
+    /// Extracts the pixels of `hdr` brighter than `threshold` and blurs them. The result
+    /// is added back to the scene by `tonemap` when it's given a bloom intensity.
+    pub fn bloom(&mut self, hdr: &Framebuffer, threshold: f32) -> Result<(), String> {
+        let width = (hdr.width() / 2).max(1);
+        let height = (hdr.height() / 2).max(1);
+        let outdated = match &self.bloom_framebuffers {
+            Some([framebuffer, _]) => framebuffer.width() != width || framebuffer.height() != height,
+            None => true,
+        };
+        if outdated {
+            // Fullscreen passes only, no depth needed
+            let spec = FramebufferSpec {
+                depth: false,
+                stencil: false,
+                ..FramebufferSpec::new(width, height, ColorFormat::Rgba16F)
+            };
+            self.bloom_framebuffers = Some([
+                Framebuffer::from_spec(&spec)?,
+                Framebuffer::from_spec(&spec)?,
+            ]);
+        }
+        let [first, second] = self.bloom_framebuffers.as_ref().unwrap();
+
+        // Blur the whole image regardless of the viewport/scissor the scene was drawn with
+        let mut viewport = [0; 4];
+        let scissor_test;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            scissor_test = gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE;
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+
+        first.bind();
+        hdr.bind_color_texture(0);
+        self.bloom_extract_shader.use_program();
+        self.bloom_extract_shader.set_uniform_1i("hdrBuffer", 0);
+        self.bloom_extract_shader.set_uniform_1f("threshold", threshold);
+        self.draw_fullscreen();
+
+        // Ping-pong between the two targets, ending back in the first one
+        self.blur_shader.use_program();
+        self.blur_shader.set_uniform_1i("image", 0);
+        for _ in 0..BLOOM_BLUR_PASSES {
+            for (source, target, horizontal) in [(first, second, true), (second, first, false)] {
+                target.bind();
+                source.bind_color_texture(0);
+                self.blur_shader.set_uniform_1i("horizontal", horizontal as i32);
+                self.draw_fullscreen();
+            }
+        }
+
+        first.unbind();
+        unsafe {
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            if scissor_test {
+                gl::Enable(gl::SCISSOR_TEST);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tone maps the pixels of `hdr` inside `region` into the viewport of the currently bound
+    /// framebuffer, adding the output of the last `bloom` call scaled by `bloom_intensity` if given
+    pub fn tonemap(
+        &self,
+        hdr: &Framebuffer,
+        region: Rect,
+        exposure: f32,
+        tone_mapping: ToneMapping,
+        bloom_intensity: Option<f32>,
+    ) {
+        hdr.bind_color_texture(0);
+        self.tonemap_shader.use_program();
+        self.tonemap_shader.set_uniform_1i("hdrBuffer", 0);
+        // Only `region` of the buffers is drawn, e.g. one half of a split view
+        let (width, height) = (hdr.width() as f32, hdr.height() as f32);
+        self.tonemap_shader.set_uniform_2f(
+            "uvOffset",
+            region.x as f32 / width,
+            region.y as f32 / height,
+        );
+        self.tonemap_shader.set_uniform_2f(
+            "uvScale",
+            region.width as f32 / width,
+            region.height as f32 / height,
+        );
+        match (bloom_intensity, &self.bloom_framebuffers) {
+            (Some(intensity), Some([bloom, _])) => {
+                bloom.bind_color_texture(1);
+                self.tonemap_shader.set_uniform_1i("bloom", 1);
+                self.tonemap_shader.set_uniform_1i("bloomBuffer", 1);
+                self.tonemap_shader.set_uniform_1f("bloomIntensity", intensity);
+            }
+            _ => self.tonemap_shader.set_uniform_1i("bloom", 0),
+        }
+        self.tonemap_shader.set_uniform_1f("exposure", exposure);
+        self.tonemap_shader
+            .set_uniform_1i("toneMapping", tone_mapping.as_uniform());
+        self.draw_fullscreen();
+    }
+
+    fn draw_fullscreen(&self) {
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
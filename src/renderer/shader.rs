@@ -1,5 +1,8 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 
 use gl::types::*;
 
@@ -31,9 +34,21 @@ impl Shader {
         Shader { id }
     }
 
+    /// Reads the shader source with its `#include "file"` directives inlined, see
+    /// `ShaderProgram::from_files`
     pub fn from_file(shader_type: ShaderType, path: &str) -> Result<Self, String> {
-        let src = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
-        Ok(Shader::new(shader_type, &src))
+        Self::from_file_with_defines(shader_type, path, &[])
+    }
+
+    /// Like `from_file`, with a `#define name value` line for each of `defines` inserted
+    /// right after the `#version` directive
+    pub fn from_file_with_defines(
+        shader_type: ShaderType,
+        path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Self, String> {
+        let src = PreprocessedSource::read(Path::new(path))?;
+        Ok(Shader::new(shader_type, &inject_defines(&src.source, defines)))
     }
 
     pub fn compile(&self) -> Result<(), String> {
@@ -47,25 +62,37 @@ impl Shader {
         }
 
         if success == 0 {
-            let mut len = 0;
-            unsafe {
-                gl::GetShaderiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-            }
+            return Err(self.info_log().unwrap_or_default());
+        }
 
-            let mut buffer = vec![0; len as usize];
-            unsafe {
-                gl::GetShaderInfoLog(
-                    self.id,
-                    len,
-                    std::ptr::null_mut(),
-                    buffer.as_mut_ptr() as *mut GLchar,
-                );
-            }
+        if LOG_INFO_ON_SUCCESS.get()
+            && let Some(log) = self.info_log()
+        {
+            println!("Shader compile log: {}", log);
+        }
+        Ok(())
+    }
 
-            return Err(String::from_utf8(buffer).unwrap());
+    /// Compiler messages of the last compile, warnings included, `None` when there were none
+    pub fn info_log(&self) -> Option<String> {
+        let mut len = 0;
+        unsafe {
+            gl::GetShaderiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
+        }
+        if len <= 1 {
+            return None;
         }
 
-        Ok(())
+        let mut buffer = vec![0; len as usize];
+        unsafe {
+            gl::GetShaderInfoLog(
+                self.id,
+                len,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr() as *mut GLchar,
+            );
+        }
+        info_log_string(buffer)
     }
 
     pub fn id(&self) -> GLuint {
@@ -81,9 +108,18 @@ impl Drop for Shader {
     }
 }
 
+thread_local! {
+    // Where linked program binaries are cached, `None` disables the cache
+    static BINARY_CACHE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    // Print the info log after successful compiles and links too, for driver warnings
+    static LOG_INFO_ON_SUCCESS: Cell<bool> = const { Cell::new(false) };
+}
+
 pub struct ShaderProgram {
     id: GLuint,
+    attached_shaders: Vec<GLuint>, // Detached once the program links
     uniforms: HashMap<Box<str>, GLint>,
+    attributes: HashMap<Box<str>, GLuint>,
     uniform_cache: RefCell<HashMap<Box<str>, UniformValue>>,
 }
 
@@ -93,15 +129,166 @@ impl ShaderProgram {
         let id = unsafe { gl::CreateProgram() };
         ShaderProgram {
             id,
+            attached_shaders: Vec::new(),
             uniforms: HashMap::new(),
+            attributes: HashMap::new(),
             uniform_cache: RefCell::new(HashMap::new()),
         }
     }
 
-    pub fn attach_shader(&self, shader: &Shader) {
+    /// Compiles and links a vertex + fragment shader pair read from disk.
+    /// `#include "file"` lines are replaced with the file, relative to the including one.
+    /// Compiler messages of included code refer to it by source string number, errors list
+    /// which file each number is.
+    pub fn from_files(vertex_path: &str, fragment_path: &str) -> Result<Self, String> {
+        Self::from_files_with_defines(vertex_path, fragment_path, &[])
+    }
+
+    /// Like `from_files`, with a `#define name value` line for each of `defines` inserted right
+    /// after the `#version` directive of both shaders. Compile time feature toggles for shader
+    /// variants, e.g. `("USE_NORMAL_MAP", "1")`.
+    pub fn from_files_with_defines(
+        vertex_path: &str,
+        fragment_path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Self, String> {
+        let vertex_src = PreprocessedSource::read(Path::new(vertex_path))?;
+        let fragment_src = PreprocessedSource::read(Path::new(fragment_path))?;
+        Self::from_sources(
+            &inject_defines(&vertex_src.source, defines),
+            &inject_defines(&fragment_src.source, defines),
+        )
+        .map_err(|e| {
+            let mut message = e;
+            for src in [&vertex_src, &fragment_src] {
+                if src.files.len() > 1 {
+                    message.push_str(&src.file_legend());
+                }
+            }
+            message
+        })
+    }
+
+    /// Compiles and links a vertex + fragment shader pair. When a binary cache directory is
+    /// set, a previously linked binary for the same sources is loaded instead.
+    pub fn from_sources(vertex_src: &str, fragment_src: &str) -> Result<Self, String> {
+        let cache_path = binary_cache_path(vertex_src, fragment_src);
+        if let Some(path) = &cache_path
+            && let Some(program) = Self::load_binary(path)
+        {
+            return Ok(program);
+        }
+
+        let vertex_shader = Shader::new(ShaderType::Vertex, vertex_src);
+        vertex_shader.compile()?;
+        let fragment_shader = Shader::new(ShaderType::Fragment, fragment_src);
+        fragment_shader.compile()?;
+
+        let mut program = ShaderProgram::new();
+        program.attach_shader(&vertex_shader);
+        program.attach_shader(&fragment_shader);
+        if cache_path.is_some() {
+            // Must be set before linking for the binary to be retrievable
+            unsafe {
+                gl::ProgramParameteri(
+                    program.id,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as GLint,
+                );
+            }
+        }
+        program.link()?;
+
+        if let Some(path) = &cache_path
+            && let Err(e) = program.save_binary(path)
+        {
+            println!("Failed to cache shader program binary: {}", e);
+        }
+
+        Ok(program)
+    }
+
+    /// Sets the directory used to cache linked program binaries, `None` disables the cache
+    pub fn set_binary_cache_dir(dir: Option<PathBuf>) {
+        BINARY_CACHE_DIR.with(|cache_dir| *cache_dir.borrow_mut() = dir);
+    }
+
+    /// Prints the compiler and linker info logs even when compiling and linking succeed,
+    /// drivers put performance warnings there. Failures always return the log.
+    pub fn set_log_info_on_success(enabled: bool) {
+        LOG_INFO_ON_SUCCESS.set(enabled);
+    }
+
+    // Returns `None` when the file is missing or the driver rejects the binary,
+    // e.g. after a driver update
+    fn load_binary(path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        if data.len() <= 4 {
+            return None;
+        }
+        let format = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        let binary = &data[4..];
+
+        let mut program = ShaderProgram::new();
+        unsafe {
+            gl::ProgramBinary(
+                program.id,
+                format,
+                binary.as_ptr().cast(),
+                binary.len() as GLsizei,
+            );
+        }
+
+        let mut success = 0;
+        unsafe {
+            gl::GetProgramiv(program.id, gl::LINK_STATUS, &mut success);
+        }
+        if success == 0 {
+            return None;
+        }
+
+        program.populate_uniform_indices();
+        program.populate_attribute_locations();
+        Some(program)
+    }
+
+    fn save_binary(&self, path: &Path) -> Result<(), String> {
+        let mut length = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return Err("Driver does not support program binaries".to_string());
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut format = 0;
+        let mut written = 0;
+        unsafe {
+            gl::GetProgramBinary(
+                self.id,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr().cast(),
+            );
+        }
+        binary.truncate(written as usize);
+
+        // File layout: binary format (u32, little endian) followed by the binary itself
+        let mut data = format.to_le_bytes().to_vec();
+        data.extend_from_slice(&binary);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, data).map_err(|e| e.to_string())
+    }
+
+    pub fn attach_shader(&mut self, shader: &Shader) {
         unsafe {
             gl::AttachShader(self.id, shader.id());
         }
+        self.attached_shaders.push(shader.id());
     }
 
     pub fn link(&mut self) -> Result<(), String> {
@@ -115,29 +302,52 @@ impl ShaderProgram {
         }
 
         if success == 0 {
-            let mut len = 0;
-            unsafe {
-                gl::GetProgramiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-            }
+            return Err(self.info_log().unwrap_or_default());
+        }
+        if LOG_INFO_ON_SUCCESS.get()
+            && let Some(log) = self.info_log()
+        {
+            println!("Shader program link log: {}", log);
+        }
 
-            let mut buffer = vec![0; len as usize];
+        // The linked program no longer needs the shader objects, detaching them lets the
+        // driver free them as soon as the `Shader`s are dropped
+        for shader in self.attached_shaders.drain(..) {
             unsafe {
-                gl::GetProgramInfoLog(
-                    self.id,
-                    len,
-                    std::ptr::null_mut(),
-                    buffer.as_mut_ptr() as *mut GLchar,
-                );
+                gl::DetachShader(self.id, shader);
             }
-
-            return Err(String::from_utf8(buffer).unwrap());
         }
 
+        // Relinking resets every uniform to its default, cached values are stale
+        self.clear_uniform_cache();
         self.populate_uniform_indices();
+        self.populate_attribute_locations();
 
         Ok(())
     }
 
+    /// Linker messages of the last link, warnings included, `None` when there were none
+    pub fn info_log(&self) -> Option<String> {
+        let mut len = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
+        }
+        if len <= 1 {
+            return None;
+        }
+
+        let mut buffer = vec![0; len as usize];
+        unsafe {
+            gl::GetProgramInfoLog(
+                self.id,
+                len,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr() as *mut GLchar,
+            );
+        }
+        info_log_string(buffer)
+    }
+
     pub fn use_program(&self) {
         unsafe {
             gl::UseProgram(self.id);
@@ -148,10 +358,10 @@ impl ShaderProgram {
         let new_value = UniformValue::from_value(value);
         {
             let cache = self.uniform_cache.borrow();
-            if let Some(cached_value) = cache.get(name) {
-                if cached_value == &new_value {
-                    return;
-                }
+            if let Some(cached_value) = cache.get(name)
+                && cached_value == &new_value
+            {
+                return;
             }
         }
 
@@ -161,6 +371,17 @@ impl ShaderProgram {
         setter();
     }
 
+    /// Forgets every cached uniform value, e.g. after the program has been relinked
+    pub fn clear_uniform_cache(&self) {
+        self.uniform_cache.borrow_mut().clear();
+    }
+
+    /// Forgets the cached value of `name` so the next set always reaches the driver.
+    /// Used for per-object values that materials sharing this program may alternate.
+    pub fn invalidate_uniform(&self, name: &str) {
+        self.uniform_cache.borrow_mut().remove(name);
+    }
+
     pub fn set_uniform_4f(&self, name: &str, x: f32, y: f32, z: f32, w: f32) {
         self.set_uniform(name, [x, y, z, w], || unsafe {
             gl::Uniform4f(self.get_uniform_location(name), x, y, z, w);
@@ -207,6 +428,12 @@ impl ShaderProgram {
         });
     }
 
+    pub fn set_uniform_2f(&self, name: &str, x: f32, y: f32) {
+        self.set_uniform(name, [x, y], || unsafe {
+            gl::Uniform2f(self.get_uniform_location(name), x, y);
+        });
+    }
+
     pub fn set_uniform_3fv(&self, name: &str, x: &[f32; 3]) {
         self.set_uniform(name, *x, || unsafe {
             gl::Uniform3fv(self.get_uniform_location(name), 1, x.as_ptr());
@@ -219,6 +446,89 @@ impl ShaderProgram {
         });
     }
 
+    // Array setters skip the uniform cache, comparing whole arrays every frame isn't worth it
+
+    pub fn set_uniform_1fv(&self, name: &str, values: &[f32]) {
+        let location = self.get_array_uniform_location(name);
+        self.invalidate_uniform(name);
+        unsafe {
+            gl::Uniform1fv(location, values.len() as GLsizei, values.as_ptr());
+        }
+    }
+
+    pub fn set_uniform_1iv(&self, name: &str, values: &[i32]) {
+        let location = self.get_array_uniform_location(name);
+        self.invalidate_uniform(name);
+        unsafe {
+            gl::Uniform1iv(location, values.len() as GLsizei, values.as_ptr());
+        }
+    }
+
+    pub fn set_uniform_3fv_array(&self, name: &str, values: &[[f32; 3]]) {
+        let location = self.get_array_uniform_location(name);
+        self.invalidate_uniform(name);
+        unsafe {
+            gl::Uniform3fv(location, values.len() as GLsizei, values.as_ptr().cast());
+        }
+    }
+
+    pub fn set_uniform_mat4_array(&self, name: &str, mats: &[glam::Mat4]) {
+        let location = self.get_array_uniform_location(name);
+        self.invalidate_uniform(name);
+        // glam matrices are plain column-major [f32; 16], so the slice can be passed as is
+        unsafe {
+            gl::UniformMatrix4fv(
+                location,
+                mats.len() as GLsizei,
+                gl::FALSE,
+                mats.as_ptr().cast(),
+            );
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Size in bytes of the uniform block `block_name` as laid out by the driver,
+    /// or `None` if the program doesn't declare it.
+    pub fn uniform_block_size(&self, block_name: &str) -> Option<usize> {
+        let block_name = CString::new(block_name).ok()?;
+        let index = unsafe { gl::GetUniformBlockIndex(self.id, block_name.as_ptr()) };
+        if index == gl::INVALID_INDEX {
+            return None;
+        }
+
+        let mut size = 0;
+        unsafe {
+            gl::GetActiveUniformBlockiv(self.id, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+        }
+        Some(size as usize)
+    }
+
+    /// Byte offset of a uniform block member (e.g. `Camera.view`) inside its block,
+    /// or `None` if the member is not active.
+    pub fn uniform_block_member_offset(&self, name: &str) -> Option<usize> {
+        let name = CString::new(name).ok()?;
+        let names = [name.as_ptr()];
+        let mut index = gl::INVALID_INDEX;
+        unsafe {
+            gl::GetUniformIndices(self.id, 1, names.as_ptr(), &mut index);
+        }
+        if index == gl::INVALID_INDEX {
+            return None;
+        }
+
+        let mut offset = -1;
+        unsafe {
+            gl::GetActiveUniformsiv(self.id, 1, &index, gl::UNIFORM_OFFSET, &mut offset);
+        }
+        if offset < 0 {
+            return None;
+        }
+        Some(offset as usize)
+    }
+
     fn get_uniform_location(&self, name: &str) -> i32 {
         if let Some(location) = self.uniforms.get(name) {
             return *location;
@@ -227,6 +537,18 @@ impl ShaderProgram {
         panic!("Uniform '{}' not found", name);
     }
 
+    // Arrays are reported by the driver as `name[0]`, accept both spellings
+    fn get_array_uniform_location(&self, name: &str) -> i32 {
+        if let Some(location) = self.uniforms.get(name) {
+            return *location;
+        }
+        if let Some(location) = self.uniforms.get(format!("{}[0]", name).as_str()) {
+            return *location;
+        }
+
+        panic!("Uniform '{}' not found", name);
+    }
+
     fn populate_uniform_indices(&mut self) {
         let mut max_length = 0;
         let mut num_active_uniforms = 0;
@@ -263,6 +585,202 @@ impl ShaderProgram {
     pub fn contains_uniform(&self, name: &str) -> bool {
         self.uniforms.contains_key(name)
     }
+
+    /// Location of the active vertex attribute `name`, or `None` if the program doesn't use it
+    pub fn attribute_location(&self, name: &str) -> Option<GLuint> {
+        self.attributes.get(name).copied()
+    }
+
+    fn populate_attribute_locations(&mut self) {
+        self.attributes.clear();
+
+        let mut max_length = 0;
+        let mut num_active_attributes = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+            gl::GetProgramiv(self.id, gl::ACTIVE_ATTRIBUTES, &mut num_active_attributes);
+        }
+
+        for i in 0..num_active_attributes {
+            let mut buffer = vec![0; max_length as usize];
+            let mut written_length = 0;
+            let mut size = 0;
+            let mut type_ = 0;
+            unsafe {
+                gl::GetActiveAttrib(
+                    self.id,
+                    i as u32,
+                    max_length,
+                    &mut written_length,
+                    &mut size,
+                    &mut type_,
+                    buffer.as_mut_ptr() as *mut GLchar,
+                );
+            }
+            let location =
+                unsafe { gl::GetAttribLocation(self.id, buffer.as_ptr() as *const GLchar) };
+            // Built-ins like gl_VertexID are reported as active but have no location
+            if location < 0 {
+                continue;
+            }
+            let attribute_name =
+                String::from_utf8(buffer[0..written_length as usize].to_vec()).unwrap();
+            self.attributes
+                .insert(attribute_name.into_boxed_str(), location as GLuint);
+        }
+    }
+}
+
+// Includes nested deeper than this are assumed to be runaway recursion
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// GLSL source with its includes inlined. `#line` directives keep the line numbers of compiler
+// messages pointing at the original files, with the file's index in `files` as source string
+// number. The including file is number 0.
+struct PreprocessedSource {
+    source: String,
+    files: Vec<PathBuf>,
+}
+
+impl PreprocessedSource {
+    fn read(path: &Path) -> Result<Self, String> {
+        let mut src = Self {
+            source: String::new(),
+            files: Vec::new(),
+        };
+        src.inline(path, &mut Vec::new())?;
+        Ok(src)
+    }
+
+    // `including` holds the files currently being inlined, outermost first
+    fn inline(&mut self, path: &Path, including: &mut Vec<PathBuf>) -> Result<(), String> {
+        if including.len() > MAX_INCLUDE_DEPTH {
+            return Err(format!(
+                "Includes nested more than {} levels deep at '{}'",
+                MAX_INCLUDE_DEPTH,
+                path.display()
+            ));
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        // Compared canonicalized so different relative paths to the same file are a cycle too
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if including.contains(&canonical) {
+            let chain: Vec<String> = including
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(format!("Include cycle: {}", chain.join(" -> ")));
+        }
+
+        let number = self.files.len();
+        self.files.push(path.to_path_buf());
+        if !including.is_empty() {
+            writeln!(self.source, "#line 1 {}", number).unwrap();
+        }
+        including.push(canonical);
+        for (i, line) in text.lines().enumerate() {
+            let Some(directive) = line.trim_start().strip_prefix("#include") else {
+                self.source.push_str(line);
+                self.source.push('\n');
+                continue;
+            };
+            let included = directive
+                .trim()
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+                .ok_or(format!(
+                    "Malformed include at {}:{}, expected #include \"file\"",
+                    path.display(),
+                    i + 1
+                ))?;
+            let included = path.parent().unwrap_or(Path::new("")).join(included);
+            self.inline(&included, including)?;
+            // Back to this file, on the line after the include
+            writeln!(self.source, "#line {} {}", i + 2, number).unwrap();
+        }
+        including.pop();
+        Ok(())
+    }
+
+    fn file_legend(&self) -> String {
+        let mut legend = String::from("\nSource string numbers:");
+        for (number, file) in self.files.iter().enumerate() {
+            write!(legend, "\n  {} = {}", number, file.display()).unwrap();
+        }
+        legend
+    }
+}
+
+// `#version` must stay the first directive, so the defines go right after it. A `#line`
+// directive then restores the numbering of the original lines for compiler messages.
+fn inject_defines(src: &str, defines: &[(&str, &str)]) -> String {
+    if defines.is_empty() {
+        return src.to_string();
+    }
+
+    let mut lines: Vec<&str> = src.lines().collect();
+    let insert_at = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map_or(0, |i| i + 1);
+    let mut injected: Vec<String> = defines
+        .iter()
+        .map(|(name, value)| format!("#define {} {}", name, value))
+        .collect();
+    injected.push(format!("#line {} 0", insert_at + 1));
+    lines.splice(insert_at..insert_at, injected.iter().map(String::as_str));
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+// Cache file for a pair of sources, the key also covers the driver since binaries aren't portable
+fn binary_cache_path(vertex_src: &str, fragment_src: &str) -> Option<PathBuf> {
+    let dir = BINARY_CACHE_DIR.with(|dir| dir.borrow().clone())?;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for part in [
+        gl_string(gl::VENDOR).as_bytes(),
+        gl_string(gl::RENDERER).as_bytes(),
+        gl_string(gl::VERSION).as_bytes(),
+        vertex_src.as_bytes(),
+        fragment_src.as_bytes(),
+    ] {
+        hash = fnv1a(hash, part);
+        hash = fnv1a(hash, &[0]); // Separator so moving text between parts changes the key
+    }
+    Some(dir.join(format!("{:016x}.bin", hash)))
+}
+
+// Info log bytes without the nul terminator and trailing whitespace, `None` if that's all there is
+fn info_log_string(mut buffer: Vec<u8>) -> Option<String> {
+    buffer.retain(|&b| b != 0);
+    let log = String::from_utf8_lossy(&buffer).trim_end().to_string();
+    (!log.is_empty()).then_some(log)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn gl_string(name: GLenum) -> String {
+    let ptr = unsafe { gl::GetString(name) };
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr.cast()) }
+        .to_string_lossy()
+        .into_owned()
 }
 
 impl Default for ShaderProgram {
@@ -284,6 +802,7 @@ enum UniformValue {
     Int(i32),
     UInt(u32),
     Float(f32),
+    VecF2([f32; 2]),
     VecF3([f32; 3]),
     VecF4([f32; 4]),
     Mat3(glam::Mat3),
@@ -314,6 +833,12 @@ impl From<f32> for UniformValue {
     }
 }
 
+impl From<[f32; 2]> for UniformValue {
+    fn from(value: [f32; 2]) -> Self {
+        UniformValue::VecF2(value)
+    }
+}
+
 impl From<[f32; 3]> for UniformValue {
     fn from(value: [f32; 3]) -> Self {
         UniformValue::VecF3(value)
@@ -337,3 +862,122 @@ impl From<glam::Mat4> for UniformValue {
         UniformValue::Mat4(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    // Fresh directory holding `files`, given as (name, contents)
+    fn shader_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shader_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn includes_are_inlined_with_line_directives() {
+        let dir = shader_dir(
+            "include",
+            &[
+                (
+                    "main.fs",
+                    "#version 450 core\n#include \"common.glsl\"\nvoid main() {}\n",
+                ),
+                ("common.glsl", "float a;\nfloat b;\n"),
+            ],
+        );
+        let src = PreprocessedSource::read(&dir.join("main.fs")).unwrap();
+        assert_eq!(
+            src.source,
+            "#version 450 core\n#line 1 1\nfloat a;\nfloat b;\n#line 3 0\nvoid main() {}\n"
+        );
+        assert_eq!(src.files, [dir.join("main.fs"), dir.join("common.glsl")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let dir = shader_dir(
+            "cycle",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"./a.glsl\"\n"),
+            ],
+        );
+        let error = PreprocessedSource::read(&dir.join("a.glsl")).err().unwrap();
+        assert!(error.starts_with("Include cycle:"), "{}", error);
+        assert!(error.ends_with("a.glsl"), "{}", error);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn includes_nested_too_deep_are_rejected() {
+        // Each file includes the next, one level more than allowed
+        let names: Vec<String> = (0..=MAX_INCLUDE_DEPTH + 1)
+            .map(|i| format!("{}.glsl", i))
+            .collect();
+        let contents: Vec<String> = (0..names.len())
+            .map(|i| match names.get(i + 1) {
+                Some(next) => format!("#include \"{}\"\n", next),
+                None => "float x;\n".to_string(),
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = names
+            .iter()
+            .zip(&contents)
+            .map(|(name, contents)| (name.as_str(), contents.as_str()))
+            .collect();
+        let dir = shader_dir("depth", &files);
+
+        let error = PreprocessedSource::read(&dir.join(&names[0])).err().unwrap();
+        assert!(error.starts_with("Includes nested more than"), "{}", error);
+        // One level less is fine
+        assert!(PreprocessedSource::read(&dir.join(&names[1])).is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Current value of the float uniform `name`, read back from the driver
+    fn uniform_value(program: &ShaderProgram, name: &str) -> f32 {
+        let mut value = 0.0;
+        unsafe {
+            gl::GetUniformfv(program.id(), program.get_uniform_location(name), &mut value);
+        }
+        value
+    }
+
+    #[test]
+    fn relinking_forgets_cached_uniforms() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let vertex = Shader::new(
+            ShaderType::Vertex,
+            "#version 450 core\nvoid main() { gl_Position = vec4(0.0); }",
+        );
+        let fragment = Shader::new(
+            ShaderType::Fragment,
+            "#version 450 core\nuniform float value;\nout vec4 color;\nvoid main() { color = vec4(value); }",
+        );
+        vertex.compile().unwrap();
+        fragment.compile().unwrap();
+        let mut program = ShaderProgram::new();
+        program.attach_shader(&vertex);
+        program.attach_shader(&fragment);
+        program.link().unwrap();
+        program.use_program();
+        program.set_uniform_1f("value", 2.0);
+        assert_eq!(uniform_value(&program, "value"), 2.0);
+
+        // Linking resets the uniform to 0, the same value must be uploaded again
+        program.attach_shader(&vertex);
+        program.attach_shader(&fragment);
+        program.link().unwrap();
+        program.use_program();
+        program.set_uniform_1f("value", 2.0);
+        assert_eq!(uniform_value(&program, "value"), 2.0);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+use crate::renderer::shader::ShaderProgram;
+
+/// Enabled features of a variant, each compiled in as `#define NAME 1`, along with
+/// `VARIANT_DEFINES`. Ordered, so the same features always map to the same variant.
+pub type DefineSet = BTreeSet<String>;
+
+/// Vertex and fragment shader files the variants of a shader are compiled from
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderSource {
+    pub vertex_path: String,
+    pub fragment_path: String,
+}
+
+impl ShaderSource {
+    pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
+        Self {
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+        }
+    }
+}
+
+/// Compiles each combination of shader files and defines once and shares the program between
+/// everything asking for it, e.g. all materials with the same set of textures
+#[derive(Default)]
+pub struct ShaderVariantCache {
+    variants: HashMap<(ShaderSource, DefineSet), Rc<ShaderProgram>>,
+}
+
+impl ShaderVariantCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The program for `source` compiled with `defines`, built on the first request
+    pub fn get(
+        &mut self,
+        source: &ShaderSource,
+        defines: &DefineSet,
+    ) -> Result<Rc<ShaderProgram>, String> {
+        let key = (source.clone(), defines.clone());
+        if let Some(program) = self.variants.get(&key) {
+            return Ok(Rc::clone(program));
+        }
+
+        // Lets shaders tell a variant apart from a program compiled without defines
+        let defines: Vec<(&str, &str)> = std::iter::once("VARIANT_DEFINES")
+            .chain(defines.iter().map(String::as_str))
+            .map(|name| (name, "1"))
+            .collect();
+        let program = ShaderProgram::from_files_with_defines(
+            &source.vertex_path,
+            &source.fragment_path,
+            &defines,
+        )
+        .map_err(|e| {
+            format!(
+                "Failed to build variant of '{}' + '{}' with {:?}: {}",
+                source.vertex_path, source.fragment_path, key.1, e
+            )
+        })?;
+        let program = Rc::new(program);
+        self.variants.insert(key, Rc::clone(&program));
+        Ok(program)
+    }
+
+    /// Number of variants compiled so far
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Forgets every variant, programs still in use by materials stay alive until they're dropped
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
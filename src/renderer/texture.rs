@@ -1,13 +1,148 @@
-use std::io::{Error, ErrorKind};
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::io::Error;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 
 use gl::types::*;
 
-use image::ImageReader;
+use image::{ColorType, ImageReader};
 use image::metadata::Orientation;
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+/// How texels are combined when a texture is magnified or minified
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest, // Crisp, blocky texels
+}
+
+/// What is sampled outside of the [0, 1] texture coordinate range
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    ClampToEdge,
+}
+
+impl TextureWrap {
+    fn as_gl_enum(&self) -> GLenum {
+        match self {
+            TextureWrap::Repeat => gl::REPEAT,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+        }
+    }
+}
+
+/// Options applied when uploading image data to a texture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureConfig {
+    /// Image holds color data in sRGB space (diffuse/albedo maps), which the GPU converts
+    /// to linear when sampling. Leave off for data maps like specular or normals.
+    pub srgb: bool,
+    /// Upload a single channel (`GL_RED`) instead of RGB, for specular or roughness maps.
+    /// Shaders should sample `.r`; the other channels are swizzled to red so `.rgb` still
+    /// reads gray. Grayscale source images are uploaded this way even when not set.
+    /// Ignored for sRGB textures, there is no single channel sRGB format.
+    pub grayscale: bool,
+    /// Generate mipmaps and filter between them, otherwise only the base level is used
+    pub mipmaps: bool,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// Offset added to the mip level picked by the GPU, positive is blurrier
+    pub lod_bias: f32,
+    /// Clamp range of the mip level, e.g. a high `min_lod` forces a blurrier look
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// Flip image files upside down when loading them. Files store the top row first while
+    /// GL puts the first row at v = 0, so this is needed for the usual OpenGL UVs but not for
+    /// glTF ones. Pixels passed in directly are never flipped.
+    pub flip_vertically: bool,
+}
+
+impl Default for TextureConfig {
+    // GL defaults for the LOD parameters
+    fn default() -> Self {
+        Self {
+            srgb: false,
+            grayscale: false,
+            mipmaps: true,
+            filter: TextureFilter::default(),
+            wrap: TextureWrap::default(),
+            lod_bias: 0.0,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+            flip_vertically: true,
+        }
+    }
+}
+
+impl TextureConfig {
+    /// Config for color textures stored in sRGB
+    pub fn srgb() -> Self {
+        Self {
+            srgb: true,
+            ..Default::default()
+        }
+    }
+
+    /// Config for single channel data maps
+    pub fn grayscale() -> Self {
+        Self {
+            grayscale: true,
+            ..Default::default()
+        }
+    }
+
+    /// Config for sRGB pixel art that stays crisp: nearest filtering, no mipmaps and no
+    /// repeating, so the texels at the edges don't bleed into each other
+    pub fn pixel_art() -> Self {
+        Self {
+            srgb: true,
+            mipmaps: false,
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::ClampToEdge,
+            ..Default::default()
+        }
+    }
+}
+
+// Pixels decoded from an image file, ready to upload
+#[derive(Debug)]
+struct DecodedImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>, // Tightly packed RGB8, or R8 when `grayscale` is set
+    grayscale: bool,
+}
+
+// Image of a `new_deferred` texture being decoded on a worker thread
+#[derive(Debug)]
+struct PendingLoad {
+    receiver: Receiver<Result<DecodedImage, String>>,
+    file_path: String,
+    config: TextureConfig,
+}
+
+#[derive(Debug)]
 pub struct Texture2D {
     id: GLuint,
+    source: RefCell<Option<(String, TextureConfig)>>, // File and config of the last load, for `reload`
+    pending: RefCell<Option<PendingLoad>>, // Uploaded when the decoded image arrives
+}
+
+// Textures are identified by their GL name
+impl PartialEq for Texture2D {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Texture2D {}
+
+impl Hash for Texture2D {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 #[allow(dead_code)]
@@ -18,53 +153,240 @@ impl Texture2D {
             gl::GenTextures(1, &mut id);
         }
 
-        Self { id }
+        Self {
+            id,
+            source: RefCell::new(None),
+            pending: RefCell::new(None),
+        }
     }
 
     pub fn new_from_file(file_path: &str) -> Result<Self, String> {
+        Self::new_from_file_with_config(file_path, &TextureConfig::default())
+    }
+
+    pub fn new_from_file_with_config(file_path: &str, config: &TextureConfig) -> Result<Self, String> {
         let texture = Self::new();
-        texture.load_file_impl(file_path).map_err(|e| e.to_string())?;
+        texture.load_file_impl(file_path, config).map_err(|e| e.to_string())?;
+        Ok(texture)
+    }
+
+    /// Like `new_from_file_with_config`, but a missing or broken file gives the checkerboard of
+    /// `default_checkerboard` instead of an error, so the rest of the scene still loads
+    pub fn new_from_file_or_default(file_path: &str, config: &TextureConfig) -> Self {
+        Self::new_from_file_with_config(file_path, config).unwrap_or_else(|e| {
+            println!("Failed to load texture '{}', using a placeholder: {}", file_path, e);
+            Self::default_checkerboard()
+        })
+    }
+
+    /// Magenta and black checkerboard that stands out on any object, the placeholder for
+    /// textures that failed to load
+    pub fn default_checkerboard() -> Self {
+        let texture = Self::new();
+        texture.upload_checkerboard();
+        texture
+    }
+
+    /// Starts decoding `file_path` on a worker thread and returns a texture that is a flat gray
+    /// until then, or the checkerboard of `default_checkerboard` if decoding fails. The pixels
+    /// are uploaded the first time the texture is bound after decoding finished, the GL calls
+    /// stay on the calling thread.
+    pub fn new_deferred(file_path: &str, config: &TextureConfig) -> Self {
+        let texture = Self::new();
+        let gray: &[u8] = if config.grayscale && !config.srgb { &[128] } else { &[128; 3] };
+        let placeholder = TextureConfig {
+            mipmaps: false,
+            ..*config
+        };
+        texture.upload(1, 1, gray, gray.len() == 1, &placeholder);
+
+        let (sender, receiver) = mpsc::channel();
+        let (path, thread_config) = (file_path.to_string(), *config);
+        std::thread::spawn(move || {
+            let decoded = decode_file(&path, &thread_config).map_err(|e| e.to_string());
+            // The texture may be gone already, nobody is waiting then
+            let _ = sender.send(decoded);
+        });
+        *texture.pending.borrow_mut() = Some(PendingLoad {
+            receiver,
+            file_path: file_path.to_string(),
+            config: *config,
+        });
+        texture
+    }
+
+    /// Whether the pixels of a `new_deferred` texture were uploaded, always true otherwise
+    pub fn is_loaded(&self) -> bool {
+        self.pending.borrow().is_none()
+    }
+
+    pub fn new_pixel_art(file_path: &str) -> Result<Self, String> {
+        Self::new_from_file_with_config(file_path, &TextureConfig::pixel_art())
+    }
+
+    /// Creates a texture from tightly packed RGB8 pixels, or R8 when `config.grayscale` is
+    /// set. The first row is sampled at v = 0.
+    pub fn new_from_pixels(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        config: &TextureConfig,
+    ) -> Result<Self, String> {
+        if config.grayscale && config.srgb {
+            return Err("Grayscale textures can't be sRGB".to_string());
+        }
+        let channels = if config.grayscale { 1 } else { 3 };
+        if pixels.len() != (width * height * channels) as usize {
+            return Err(format!(
+                "Expected {} bytes of pixel data for a {}x{} texture, got {}",
+                width * height * channels,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        let texture = Self::new();
+        texture.upload(width, height, pixels, config.grayscale, config);
         Ok(texture)
     }
 
     pub fn load_file(&self, file_path: &str) -> Result<(), String> {
-        self.load_file_impl(file_path).map_err(|e| e.to_string())
+        self.load_file_with_config(file_path, &TextureConfig::default())
+    }
+
+    pub fn load_file_with_config(&self, file_path: &str, config: &TextureConfig) -> Result<(), String> {
+        self.load_file_impl(file_path, config).map_err(|e| e.to_string())
+    }
+
+    /// Reads the file this texture was last loaded from again and re-uploads it into the
+    /// same GL texture, so materials using it see the change. On error the old contents stay.
+    pub fn reload(&self) -> Result<(), String> {
+        let (file_path, config) = self
+            .source
+            .borrow()
+            .clone()
+            .ok_or("Texture was not loaded from a file")?;
+        self.load_file_impl(&file_path, &config).map_err(|e| e.to_string())
     }
 
     pub fn bind_slot(&self, slot: u32) {
+        self.poll_deferred();
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + slot);
             gl::BindTexture(gl::TEXTURE_2D, self.id);
         }
     }
 
-    fn load_file_impl(&self, file_path: &str) -> Result<(), Error> {
-        let loader = ImageReader::open(file_path)?;
-        let mut image = loader.decode().map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
-        image.apply_orientation(Orientation::FlipVertical);
-        let image = image.into_rgb8();
+    // Uploads the image of a deferred texture if the worker thread is done with it
+    fn poll_deferred(&self) {
+        let result = match self.pending.borrow().as_ref() {
+            None => return,
+            Some(pending) => match pending.receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("Decoding thread stopped".to_string()),
+            },
+        };
+
+        let PendingLoad { file_path, config, .. } = self.pending.borrow_mut().take().unwrap();
+        match result {
+            Ok(image) => {
+                self.upload(image.width, image.height, &image.data, image.grayscale, &config);
+                *self.source.borrow_mut() = Some((file_path, config));
+            }
+            Err(e) => {
+                println!("Failed to load texture '{}', using a placeholder: {}", file_path, e);
+                self.upload_checkerboard();
+            }
+        }
+    }
+
+    fn load_file_impl(&self, file_path: &str, config: &TextureConfig) -> Result<(), Error> {
+        let image = decode_file(file_path, config)?;
+        self.upload(image.width, image.height, &image.data, image.grayscale, config);
+
+        *self.source.borrow_mut() = Some((file_path.to_string(), *config));
+
+        Ok(())
+    }
+
+    fn upload_checkerboard(&self) {
+        const SIZE: u32 = 64;
+        const SQUARE: u32 = 8;
+        let data: Vec<u8> = (0..SIZE * SIZE)
+            .flat_map(|i| {
+                let (x, y) = (i % SIZE, i / SIZE);
+                if (x / SQUARE + y / SQUARE).is_multiple_of(2) {
+                    [255, 0, 255]
+                } else {
+                    [0, 0, 0]
+                }
+            })
+            .collect();
+        // Sharp squares at any distance
+        let config = TextureConfig {
+            mipmaps: false,
+            filter: TextureFilter::Nearest,
+            ..Default::default()
+        };
+        self.upload(SIZE, SIZE, &data, false, &config);
+    }
+
+    // `data` is tightly packed RGB8, or R8 when `grayscale` is set
+    fn upload(&self, width: u32, height: u32, data: &[u8], grayscale: bool, config: &TextureConfig) {
+        let (internal_format, format) = match (grayscale, config.srgb) {
+            (true, _) => (gl::R8, gl::RED),
+            (false, true) => (gl::SRGB8, gl::RGB),
+            (false, false) => (gl::RGB8, gl::RGB),
+        };
+        // Grayscale textures read as gray from every channel
+        let swizzle = if grayscale {
+            [gl::RED, gl::RED, gl::RED, gl::ONE]
+        } else {
+            [gl::RED, gl::GREEN, gl::BLUE, gl::ALPHA]
+        }
+        .map(|c| c as GLint);
 
+        // Restored afterwards, the active unit may hold another material's texture
+        let mut previous = 0;
         unsafe {
+            gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut previous);
             gl::BindTexture(gl::TEXTURE_2D, self.id);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            let wrap = config.wrap.as_gl_enum() as GLint;
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap);
+            let (min_filter, mag_filter) = match (config.filter, config.mipmaps) {
+                (TextureFilter::Linear, true) => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+                (TextureFilter::Linear, false) => (gl::LINEAR, gl::LINEAR),
+                (TextureFilter::Nearest, true) => (gl::NEAREST_MIPMAP_NEAREST, gl::NEAREST),
+                (TextureFilter::Nearest, false) => (gl::NEAREST, gl::NEAREST),
+            };
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+            gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_LOD_BIAS, config.lod_bias);
+            gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_MIN_LOD, config.min_lod);
+            gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_MAX_LOD, config.max_lod);
+            gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            // Rows of tightly packed RGB8/R8 data aren't 4-byte aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGB as GLint,
-                image.width() as GLint,
-                image.height() as GLint,
+                internal_format as GLint,
+                width as GLint,
+                height as GLint,
                 0,
-                gl::RGB,
+                format,
                 gl::UNSIGNED_BYTE,
-                image.as_ptr() as *const _,
+                data.as_ptr() as *const _,
             );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if config.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, previous as GLuint);
         }
-
-        Ok(())
     }
 
     pub fn id(&self) -> GLuint {
@@ -72,6 +394,36 @@ impl Texture2D {
     }
 }
 
+// Reads and decodes an image file, doesn't touch GL so it can run on any thread
+fn decode_file(file_path: &str, config: &TextureConfig) -> Result<DecodedImage, Error> {
+    let loader = ImageReader::open(file_path)?;
+    let mut image = loader.decode().map_err(|e| Error::other(e.to_string()))?;
+    if config.flip_vertically {
+        image.apply_orientation(Orientation::FlipVertical);
+    }
+    let (width, height) = (image.width(), image.height());
+
+    let grayscale_source = matches!(image.color(), ColorType::L8 | ColorType::L16);
+    let grayscale = (config.grayscale || grayscale_source) && !config.srgb;
+    let data = if grayscale {
+        image.into_luma8().into_raw()
+    } else {
+        image.into_rgb8().into_raw()
+    };
+    Ok(DecodedImage {
+        width,
+        height,
+        data,
+        grayscale,
+    })
+}
+
+impl Default for Texture2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for Texture2D {
     fn drop(&mut self) {
         unsafe {
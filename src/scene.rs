@@ -1,287 +1,263 @@
 pub mod camera;
 pub mod light;
+mod loader;
 pub mod object;
 
-pub use camera::Camera;
-pub use light::Light;
-pub use object::{Object, Transform};
+pub use camera::{Camera, CameraPose, ZoomMode};
+pub use light::{Falloff, Light};
+pub use loader::load_gltf;
+pub use object::{Animator, Object, Transform};
 
+use std::collections::HashMap;
+use std::time::Duration;
 use std::{cell::RefCell, rc::Rc};
 
 use crate::renderer::RenderInfo;
-use crate::renderer::material::{Material, MaterialProperty};
-use crate::renderer::mesh::{Mesh, Vertex};
-use crate::renderer::shader::{Shader, ShaderProgram, ShaderType};
-use crate::renderer::texture::Texture2D;
+use crate::renderer::material::Material;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::shader_variants::ShaderVariantCache;
 
 pub struct AmbientLight {
     pub color: glam::Vec3,
     pub intensity: f32,
 }
 
+// Unlit cube drawn at a light's position, tinted with the light's color
+struct LightEmitter {
+    object: Rc<RefCell<Object>>,
+    light: Rc<RefCell<Light>>,
+}
+
 pub struct Scene {
-    pub camera: Camera,
+    cameras: Vec<Camera>, // Never empty
+    active_camera: usize,
     pub objects: Vec<Rc<RefCell<Object>>>,
     pub lights: Vec<Rc<RefCell<Light>>>,
     pub ambient_light: AmbientLight,
-    light_materials: Vec<Rc<RefCell<Material>>>,
+    pub selected_object: Option<usize>, // Index into `objects`, drawn with an outline
+    light_emitters: Vec<LightEmitter>,
+    // Registered by name so objects can be spawned without holding on to the resources
+    meshes: HashMap<String, Rc<Mesh>>,
+    materials: HashMap<String, Rc<RefCell<Material>>>,
+    shader_variants: Rc<RefCell<ShaderVariantCache>>, // Dropped with the scene
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
-            camera: Camera::new(),
+            cameras: vec![Camera::new()],
+            active_camera: 0,
             objects: Vec::new(),
             lights: Vec::new(),
             ambient_light: AmbientLight {
                 color: glam::Vec3::new(1.0, 1.0, 1.0),
                 intensity: 0.0,
             },
-            light_materials: Vec::new(),
+            selected_object: None,
+            light_emitters: Vec::new(),
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            shader_variants: Rc::new(RefCell::new(ShaderVariantCache::new())),
         }
     }
 
-    pub fn init(&mut self) -> Result<(), String> {
-        let cube_vertices: [Vertex; 36] = [
-            Vertex([-0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 0.0]),
-            Vertex([0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 0.0]),
-            Vertex([0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 1.0]),
-            Vertex([0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [1.0, 1.0]),
-            Vertex([-0.5, 0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 1.0]),
-            Vertex([-0.5, -0.5, -0.5], [0.0, 0.0, -1.0], [0.0, 0.0]),
-            Vertex([-0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 0.0]),
-            Vertex([0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 0.0]),
-            Vertex([0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 1.0]),
-            Vertex([0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 1.0]),
-            Vertex([-0.5, 0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 1.0]),
-            Vertex([-0.5, -0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 0.0]),
-            Vertex([-0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], [1.0, 0.0]),
-            Vertex([-0.5, 0.5, -0.5], [-1.0, 0.0, 0.0], [1.0, 1.0]),
-            Vertex([-0.5, -0.5, -0.5], [-1.0, 0.0, 0.0], [0.0, 1.0]),
-            Vertex([-0.5, -0.5, -0.5], [-1.0, 0.0, 0.0], [0.0, 1.0]),
-            Vertex([-0.5, -0.5, 0.5], [-1.0, 0.0, 0.0], [0.0, 0.0]),
-            Vertex([-0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], [1.0, 0.0]),
-            Vertex([0.5, 0.5, 0.5], [1.0, 0.0, 0.0], [1.0, 0.0]),
-            Vertex([0.5, 0.5, -0.5], [1.0, 0.0, 0.0], [1.0, 1.0]),
-            Vertex([0.5, -0.5, -0.5], [1.0, 0.0, 0.0], [0.0, 1.0]),
-            Vertex([0.5, -0.5, -0.5], [1.0, 0.0, 0.0], [0.0, 1.0]),
-            Vertex([0.5, -0.5, 0.5], [1.0, 0.0, 0.0], [0.0, 0.0]),
-            Vertex([0.5, 0.5, 0.5], [1.0, 0.0, 0.0], [1.0, 0.0]),
-            Vertex([-0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [0.0, 1.0]),
-            Vertex([0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [1.0, 1.0]),
-            Vertex([0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [1.0, 0.0]),
-            Vertex([0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [1.0, 0.0]),
-            Vertex([-0.5, -0.5, 0.5], [0.0, -1.0, 0.0], [0.0, 0.0]),
-            Vertex([-0.5, -0.5, -0.5], [0.0, -1.0, 0.0], [0.0, 1.0]),
-            Vertex([-0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [0.0, 1.0]),
-            Vertex([0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [1.0, 1.0]),
-            Vertex([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [1.0, 0.0]),
-            Vertex([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [1.0, 0.0]),
-            Vertex([-0.5, 0.5, 0.5], [0.0, 1.0, 0.0], [0.0, 0.0]),
-            Vertex([-0.5, 0.5, -0.5], [0.0, 1.0, 0.0], [0.0, 1.0]),
-        ];
-
-        // ==== Shaders ====
-        // Object rendering shader
-        let vertex_shader = Shader::from_file(ShaderType::Vertex, "./shaders/basic_vertex.vs")?;
-        vertex_shader.compile()?;
-
-        let fragment_shader =
-            Shader::from_file(ShaderType::Fragment, "./shaders/basic_fragment.fs")?;
-        fragment_shader.compile()?;
-
-        let mut shader = ShaderProgram::new();
-        shader.attach_shader(&vertex_shader);
-        shader.attach_shader(&fragment_shader);
-        shader.link()?;
-
-        let objects_shader = Rc::new(shader);
-
-        // Light source rendering shader
-        let vertex_shader = Shader::from_file(ShaderType::Vertex, "./shaders/light_source.vs")?;
-        vertex_shader.compile()?;
-        let fragment_shader = Shader::from_file(ShaderType::Fragment, "./shaders/light_source.fs")?;
-        fragment_shader.compile()?;
-        let mut light_shader = ShaderProgram::new();
-        light_shader.attach_shader(&vertex_shader);
-        light_shader.attach_shader(&fragment_shader);
-        light_shader.link()?;
-
-        let light_shader = Rc::new(light_shader);
-
-        // ==== Textures ====
-        let container_texture_diffuse =
-            Rc::new(Texture2D::new_from_file("./textures/container2.png")?);
-        let container_texture_specular = Rc::new(Texture2D::new_from_file(
-            "./textures/container2_specular.png",
-        )?);
-
-        // ==== Meshes ====
-        let mut cube_mesh = Mesh::new();
-        cube_mesh.init(&cube_vertices, None);
-        let cube_mesh = Rc::new(cube_mesh);
-
-        // ==== Materials ====
-        let phong_material = Rc::new(RefCell::new(Material::new_with_properties(
-            "phong_textured",
-            Rc::clone(&objects_shader),
-            [
-                (
-                    "material.diffuse".to_string(),
-                    MaterialProperty::Texture(Rc::clone(&container_texture_diffuse)),
-                ),
-                (
-                    "material.specular".to_string(),
-                    MaterialProperty::Texture(Rc::clone(&container_texture_specular)),
-                ),
-                (
-                    "material.shininess".to_string(),
-                    MaterialProperty::Integer(32),
-                ),
-                ("isFloor".to_string(), MaterialProperty::Boolean(false)),
-                (
-                    "floorColor".to_string(),
-                    MaterialProperty::Color(0.5, 0.5, 0.5),
-                ),
-            ]
-            .into(),
-        )));
-
-        let light_material = Rc::new(RefCell::new(Material::new(
-            "light_source",
-            Rc::clone(&light_shader),
-        )));
-
-        self.light_materials.push(Rc::clone(&light_material));
-
-        // ==== Scene ====
-        let cube_positions = [
-            glam::Vec3::new(0.0, 0.0, 0.0),
-            glam::Vec3::new(2.0, 5.0, -15.0),
-            glam::Vec3::new(-1.5, -2.2, -2.5),
-            glam::Vec3::new(-3.8, -2.0, -12.3),
-            glam::Vec3::new(2.4, -0.4, -3.5),
-            glam::Vec3::new(-1.7, 3.0, -7.5),
-            glam::Vec3::new(1.3, -2.0, -2.5),
-            glam::Vec3::new(1.5, 2.0, -2.5),
-            glam::Vec3::new(1.5, 0.2, -1.5),
-            glam::Vec3::new(-1.3, 1.0, -1.5),
-        ];
-
-        for position in cube_positions {
-            let cube = Rc::new(RefCell::new(Object::new(
-                Rc::clone(&cube_mesh),
-                Rc::clone(&phong_material),
-            )));
-            cube.borrow_mut().transform.position = position;
-            cube.borrow_mut().rotate = true;
-            self.add_object(Rc::clone(&cube));
-        }
+    /// The camera the scene is rendered and controlled from
+    pub fn camera(&self) -> &Camera {
+        &self.cameras[self.active_camera]
+    }
 
-        // Floor
-        let floor = Rc::new(RefCell::new(Object::new(
-            Rc::clone(&cube_mesh),
-            Rc::clone(&phong_material),
-        )));
-        {
-            let mut floor = floor.borrow_mut();
-            floor.transform.position = glam::vec3(0.0, -3.0, 0.0);
-            floor.transform.scale = glam::Vec3::new(50.0, 0.1, 50.0);
-            floor.material_overrides.set_boolean("isFloor", true);
-        }
-        self.add_object(Rc::clone(&floor));
-
-        // Light sources
-        let light_positions = [
-            glam::Vec3::new(0.7, 0.2, 2.0),
-            glam::Vec3::new(2.3, 10.3, -4.0),
-            glam::Vec3::new(-4.0, 2.0, -12.0),
-            glam::Vec3::new(0.0, 0.0, -3.0),
-        ];
-
-        for position in light_positions {
-            // Light source object
-            let light = Rc::new(RefCell::new(Object::new(
-                Rc::clone(&cube_mesh),
-                Rc::clone(&light_material),
-            )));
-            {
-                let mut light = light.borrow_mut();
-                light.transform.position = position;
-                light.transform.scale = glam::Vec3::splat(0.2);
-            }
-            self.add_object(Rc::clone(&light));
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.cameras[self.active_camera]
+    }
 
-            // Actual Light
-            let light = Rc::new(RefCell::new(Light::new_point_light()));
-            {
-                let mut light = light.borrow_mut();
-                light.position = position;
-            }
-            self.add_light(light);
-        }
+    pub fn cameras(&self) -> &[Camera] {
+        &self.cameras
+    }
 
-        // Directional light
-        let light = Rc::new(RefCell::new(Light::new_directional_light()));
-        light.borrow_mut().intensity = 0.4;
-        light
-            .borrow_mut()
-            .as_directional_light_mut()
-            .unwrap()
-            .direction = glam::Vec3::new(-0.2, -1.0, -0.3);
-        self.add_light(light);
+    pub fn cameras_mut(&mut self) -> &mut [Camera] {
+        &mut self.cameras
+    }
 
-        // Flashlight
-        let light = Rc::new(RefCell::new(Light::new_spot_light()));
-        self.add_light(light);
+    /// Adds a camera and returns its index, the active camera doesn't change
+    pub fn add_camera(&mut self, camera: Camera) -> usize {
+        self.cameras.push(camera);
+        self.cameras.len() - 1
+    }
 
+    pub fn set_active_camera(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.cameras.len() {
+            return Err(format!(
+                "Camera {} doesn't exist, the scene has {} cameras",
+                index,
+                self.cameras.len()
+            ));
+        }
+        self.active_camera = index;
         Ok(())
     }
 
+    pub fn active_camera(&self) -> usize {
+        self.active_camera
+    }
+
     pub fn add_object(&mut self, object: Rc<RefCell<Object>>) {
         self.objects.push(object);
     }
 
+    /// Makes `mesh` available to `spawn` as `name`, replacing any mesh already registered with it
+    pub fn register_mesh(&mut self, name: &str, mesh: Rc<Mesh>) {
+        self.meshes.insert(name.to_string(), mesh);
+    }
+
+    /// Makes `material` available to `spawn` as `name`, replacing any material already registered with it
+    pub fn register_material(&mut self, name: &str, material: Rc<RefCell<Material>>) {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<Rc<Mesh>> {
+        self.meshes.get(name).map(Rc::clone)
+    }
+
+    pub fn material(&self, name: &str) -> Option<Rc<RefCell<Material>>> {
+        self.materials.get(name).map(Rc::clone)
+    }
+
+    /// Shader variants shared by the scene's materials, see `Material::with_variants`
+    pub fn shader_variants(&self) -> Rc<RefCell<ShaderVariantCache>> {
+        Rc::clone(&self.shader_variants)
+    }
+
+    /// Adds an object drawing the registered mesh with the registered material and returns it
+    pub fn spawn(
+        &mut self,
+        mesh_name: &str,
+        material_name: &str,
+    ) -> Result<Rc<RefCell<Object>>, String> {
+        let mesh = self
+            .mesh(mesh_name)
+            .ok_or(format!("No mesh registered as '{}'", mesh_name))?;
+        let material = self
+            .material(material_name)
+            .ok_or(format!("No material registered as '{}'", material_name))?;
+        let object = Rc::new(RefCell::new(Object::new(mesh, material)));
+        self.add_object(Rc::clone(&object));
+        Ok(object)
+    }
+
+    /// Index into `objects` of the object with `id`, e.g. one returned by `Renderer::pick`
+    pub fn object_index(&self, id: u32) -> Option<usize> {
+        self.objects.iter().position(|object| {
+            object
+                .try_borrow()
+                .expect("Object mutably borrowed while looking up its id")
+                .id()
+                == id
+        })
+    }
+
+    /// Objects in the order they're drawn: grouped by material so state changes are shared,
+    /// groups ordered by the first object using their material, and insertion order within a
+    /// group. The order only depends on how the scene was built, so renders are reproducible.
+    pub fn render_order(&self) -> Vec<Rc<RefCell<Object>>> {
+        let mut materials: Vec<Rc<RefCell<Material>>> = Vec::new();
+        let mut order = Vec::with_capacity(self.objects.len());
+        for object in &self.objects {
+            let material = object
+                .try_borrow()
+                .expect("Object mutably borrowed while sorting for rendering")
+                .material();
+            let group = match materials.iter().position(|m| Rc::ptr_eq(m, &material)) {
+                Some(group) => group,
+                None => {
+                    materials.push(material);
+                    materials.len() - 1
+                }
+            };
+            order.push((group, Rc::clone(object)));
+        }
+        // Stable, so objects sharing a material keep their insertion order
+        order.sort_by_key(|(group, _)| *group);
+        order.into_iter().map(|(_, object)| object).collect()
+    }
+
+    /// Adds a copy of `object` sharing its mesh and material, with the same transform and
+    /// overrides but nudged along X so both can be seen. The animator isn't copied.
+    pub fn duplicate_object(&mut self, object: &Rc<RefCell<Object>>) -> Rc<RefCell<Object>> {
+        let duplicate = {
+            let object = object.borrow();
+            let mut duplicate = Object::new(object.mesh(), object.material());
+            duplicate.transform = object.transform.clone();
+            duplicate.transform.position.x += 1.0;
+            duplicate.material_overrides = object.material_overrides.clone();
+            duplicate.visible = object.visible;
+            Rc::new(RefCell::new(duplicate))
+        };
+        self.add_object(Rc::clone(&duplicate));
+        duplicate
+    }
+
     pub fn add_light(&mut self, light: Rc<RefCell<Light>>) {
         self.lights.push(light);
     }
 
-    pub fn update(&mut self, render_info: &RenderInfo) {
-        self.camera.update(render_info);
-
-        // Rotate cubes, a bit hacky
-        let mut i = 0;
-        for cube in self.objects.iter() {
-            let mut cube = cube.borrow_mut();
-            if !cube.rotate {
-                continue;
-            }
+    /// Keeps the `color` property of `object` in sync with `light`'s color on every update,
+    /// e.g. for an unlit cube marking where a point light is. `object` still has to be added.
+    pub fn add_light_emitter(&mut self, object: Rc<RefCell<Object>>, light: Rc<RefCell<Light>>) {
+        self.light_emitters.push(LightEmitter { object, light });
+    }
 
-            let angle = (20.0 * i as f32).to_radians();
-            let axis = glam::Vec3::new(1.0, 0.3, 0.5).normalize();
-            let quat = glam::Quat::from_axis_angle(axis, render_info.time.as_secs_f32() * angle);
-            cube.transform.rotation = quat;
-            i += 1;
+    /// Advances the simulation, animators included, to `time`. Called at a fixed rate.
+    pub fn fixed_update(&mut self, time: Duration) {
+        let time = time.as_secs_f32();
+        for object in &self.objects {
+            object
+                .try_borrow_mut()
+                .expect("Object borrowed elsewhere during the fixed update")
+                .fixed_update(time);
         }
+    }
 
+    /// Blends animated objects between their last two fixed updates, `alpha` in [0, 1]
+    pub fn interpolate(&mut self, alpha: f32) {
+        for object in &self.objects {
+            object
+                .try_borrow_mut()
+                .expect("Object borrowed elsewhere during interpolation")
+                .interpolate(alpha);
+        }
+    }
+
+    /// Per frame work that follows the input, like the camera
+    pub fn update(&mut self, render_info: &RenderInfo) {
+        // Only the active camera follows the input
+        self.camera_mut().update(render_info);
+
+        let (position, direction) = (self.camera().position(), self.camera().direction());
         for light in &self.lights {
-            let mut light = light.borrow_mut();
-            light.color = render_info.ui.light_color;
-            if light.is_spot_light() {
-                light.as_spot_light_mut().unwrap().direction = self.camera.direction();
-                light.position = self.camera.position();
+            let mut light = light
+                .try_borrow_mut()
+                .expect("Light borrowed elsewhere during the scene update");
+            if let Some(spot) = light.as_spot_light_mut()
+                && spot.attached_to_camera
+            {
+                spot.direction = direction;
+                light.position = position;
             }
         }
 
-        // Color of the light emitter, still one color for all lights
-        if let Some(material) = self.light_materials.first() {
-            let shader = material.borrow_mut().shader();
-            shader.use_program();
-            let (r, g, b) = render_info.ui.light_color.into();
-            material
-                .borrow_mut()
-                .properties_mut()
-                .set_color("lightColor", r, g, b);
+        // Tint each emitter with the color of its light, the light borrow ends before the object's
+        for emitter in &self.light_emitters {
+            let [r, g, b] = emitter
+                .light
+                .try_borrow()
+                .expect("Emitter light mutably borrowed during the scene update")
+                .color;
+            emitter
+                .object
+                .try_borrow_mut()
+                .expect("Emitter object borrowed elsewhere during the scene update")
+                .material_overrides
+                .set_vec3("color", [r, g, b]);
         }
     }
 }
@@ -291,3 +267,70 @@ impl Default for Scene {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use crate::input::InputManager;
+    use crate::test_support;
+    use crate::ui::Ui;
+
+    #[test]
+    fn update_moves_camera_and_attached_light_with_a_manual_clock() {
+        let (input_manager, ui) = (InputManager::default(), Ui::default());
+        let clock = ManualClock::new();
+        let mut scene = Scene::new();
+        let flashlight = Rc::new(RefCell::new(Light::new_spot_light()));
+        flashlight.borrow_mut().as_spot_light_mut().unwrap().attached_to_camera = true;
+        scene.add_light(Rc::clone(&flashlight));
+        let target = CameraPose {
+            position: glam::Vec3::new(0.0, 3.0, 1.0),
+            ..scene.camera().pose()
+        };
+        scene.camera_mut().animate_to(target, Duration::from_secs(2));
+
+        let mut last = clock.now();
+        for _ in 0..4 {
+            clock.advance(Duration::from_millis(500));
+            let now = clock.now();
+            scene.update(&RenderInfo {
+                dt: now - last,
+                time: now,
+                input_manager: &input_manager,
+                ui: &ui,
+            });
+            last = now;
+            assert_eq!(flashlight.borrow().position, scene.camera().position());
+        }
+        assert_eq!(scene.camera().pose(), target);
+    }
+
+    #[test]
+    fn render_order_groups_by_material_in_insertion_order() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let shader = test_support::unlit_shader();
+        let red = Rc::new(RefCell::new(Material::unlit(Rc::clone(&shader), [1.0, 0.0, 0.0])));
+        let blue = Rc::new(RefCell::new(Material::unlit(shader, [0.0, 0.0, 1.0])));
+        let mut scene = Scene::new();
+        scene.register_mesh("quad", test_support::quad_mesh());
+        scene.register_material("red", red);
+        scene.register_material("blue", blue);
+        let objects: Vec<_> = ["red", "blue", "red", "blue", "red"]
+            .into_iter()
+            .map(|material| scene.spawn("quad", material).unwrap())
+            .collect();
+
+        let order = scene.render_order();
+        let expected = [0, 2, 4, 1, 3];
+        assert_eq!(order.len(), expected.len());
+        for (object, index) in order.iter().zip(expected) {
+            assert!(Rc::ptr_eq(object, &objects[index]));
+        }
+        // Nothing changed, so neither does the order
+        let again = scene.render_order();
+        assert!(order.iter().zip(&again).all(|(a, b)| Rc::ptr_eq(a, b)));
+    }
+}
@@ -1,8 +1,27 @@
+use std::time::Duration;
+
 use glam::{Mat4, Vec3};
-use winit::keyboard::KeyCode;
 
+use crate::input::Action;
 use crate::renderer::RenderInfo;
 
+/// Where a camera is and where it looks, enough to restore a viewpoint
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub yaw: f32,   // In degrees
+    pub pitch: f32, // In degrees
+    pub fov: f32,   // In degrees
+}
+
+/// What the mouse wheel does to the camera
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ZoomMode {
+    #[default]
+    Fov,   // Narrows or widens the field of view
+    Dolly, // Moves the camera along its direction, the field of view stays fixed
+}
+
 pub struct Camera {
     position: Vec3,
     direction: Vec3,
@@ -17,6 +36,16 @@ pub struct Camera {
     fov: f32,
     near: f32,
     far: f32,
+    zoom_mode: ZoomMode,
+    animation: Option<Animation>,
+}
+
+// Transition started by `animate_to`
+struct Animation {
+    from: CameraPose,
+    to: CameraPose,
+    duration: Duration,
+    elapsed: Duration,
 }
 
 impl Camera {
@@ -34,27 +63,108 @@ impl Camera {
             fov: 45.0,
             near: 0.1,
             far: 100.0,
+            zoom_mode: ZoomMode::default(),
+            animation: None,
         };
         m.view_matrix = Mat4::look_to_rh(m.position, m.direction, m.up);
         m
     }
 
     pub fn update(&mut self, args: &RenderInfo) {
+        if self.animation.is_some() {
+            if self.has_user_input(args) {
+                self.animation = None;
+            } else {
+                self.update_animation(args.dt);
+                return;
+            }
+        }
+
         self.update_direction(args);
         self.update_position(args);
-        self.view_matrix = Mat4::look_to_rh(self.position, self.direction, self.up);
+        self.update_zoom(args);
+        self.update_matrices();
+    }
 
-        self.update_projection(args);
+    /// Rebuilds the view and projection matrices from the current state, without reading input
+    pub fn update_matrices(&mut self) {
+        self.view_matrix = Mat4::look_to_rh(self.position, self.direction, self.up);
+        let aspect = self.width as f32 / self.height as f32;
+        self.projection_matrix = Mat4::perspective_rh_gl(self.fov.to_radians(), aspect, self.near, self.far);
     }
 
     pub fn position(&self) -> Vec3 {
         self.position
     }
 
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Points the camera using yaw and pitch angles in degrees, pitch is clamped to avoid flipping
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-89.0, 89.0);
+        self.direction = direction_from_angles(self.yaw, self.pitch);
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov: self.fov,
+        }
+    }
+
+    /// Moves the camera to `pose` immediately and rebuilds its matrices
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.position = pose.position;
+        self.set_orientation(pose.yaw, pose.pitch);
+        self.fov = pose.fov.clamp(1.0, 45.0);
+        self.update_matrices();
+    }
+
+    /// Moves the camera to `target` over `duration` during the following `update` calls.
+    /// Moving the camera by hand cancels the animation where it is.
+    pub fn animate_to(&mut self, target: CameraPose, duration: Duration) {
+        if duration.is_zero() {
+            self.animation = None;
+            self.set_pose(target);
+            return;
+        }
+        self.animation = Some(Animation {
+            from: self.pose(),
+            to: target,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some()
+    }
+
     pub fn direction(&self) -> Vec3 {
         self.direction
     }
 
+    pub fn zoom_mode(&self) -> ZoomMode {
+        self.zoom_mode
+    }
+
+    pub fn set_zoom_mode(&mut self, mode: ZoomMode) {
+        self.zoom_mode = mode;
+    }
+
     pub fn view_matrix(&self) -> &Mat4 {
         &self.view_matrix
     }
@@ -63,14 +173,56 @@ impl Camera {
         &self.projection_matrix
     }
 
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.width = width;
         self.height = height;
     }
 
+    fn update_animation(&mut self, dt: Duration) {
+        let Some(animation) = self.animation.as_mut() else {
+            return;
+        };
+        animation.elapsed += dt;
+        if animation.elapsed >= animation.duration {
+            let target = animation.to;
+            self.animation = None;
+            self.set_pose(target);
+            return;
+        }
+
+        let t = animation.elapsed.as_secs_f32() / animation.duration.as_secs_f32();
+        let (from, to) = (animation.from, animation.to);
+        // Yaw goes the short way around instead of spinning through up to 360 degrees
+        let yaw_delta = (to.yaw - from.yaw + 180.0).rem_euclid(360.0) - 180.0;
+        self.set_pose(CameraPose {
+            position: from.position.lerp(to.position, t),
+            yaw: from.yaw + yaw_delta * t,
+            pitch: from.pitch + (to.pitch - from.pitch) * t,
+            fov: from.fov + (to.fov - from.fov) * t,
+        });
+    }
+
+    fn has_user_input(&self, args: &RenderInfo) -> bool {
+        let input = &args.input_manager;
+        let mouse_delta = input.mouse_delta();
+        let looking = args.ui.is_mouse_looking(input)
+            && (mouse_delta.0 != 0.0 || mouse_delta.1 != 0.0);
+        looking
+            || input.mouse_wheel_delta() != 0.0
+            || Action::ALL.iter().any(|action| input.is_action_pressed(*action))
+    }
+
     fn update_direction(&mut self, args: &RenderInfo) {
         let input = &args.input_manager;
-        if !input.is_mouse_button_pressed(winit::event::MouseButton::Right) {
+        if !args.ui.is_mouse_looking(input) {
             return;
         }
         let mouse_delta = input.mouse_delta();
@@ -78,49 +230,102 @@ impl Camera {
         self.yaw += mouse_delta.0 as f32 * sensitivity;
         self.pitch -= mouse_delta.1 as f32 * sensitivity;
         self.pitch = self.pitch.clamp(-89.0, 89.0);
-
-        let direction = Vec3::new(
-            self.yaw.to_radians().cos() * self.pitch.to_radians().cos(),
-            self.pitch.to_radians().sin(),
-            self.yaw.to_radians().sin() * self.pitch.to_radians().cos(),
-        );
-        self.direction = direction.normalize();
+        self.direction = direction_from_angles(self.yaw, self.pitch);
     }
 
     fn update_position(&mut self, args: &RenderInfo) {
         let input = &args.input_manager;
         let speed = args.ui.camera_speed * args.dt.as_secs_f32();
-        if input.is_key_pressed(KeyCode::KeyW) {
+        if input.is_action_pressed(Action::MoveForward) {
             self.position += self.direction * speed;
         }
-        if input.is_key_pressed(KeyCode::KeyS) {
+        if input.is_action_pressed(Action::MoveBackward) {
             self.position -= self.direction * speed;
         }
-        if input.is_key_pressed(KeyCode::KeyA) {
+        if input.is_action_pressed(Action::MoveLeft) {
             self.position -= self.direction.cross(self.up).normalize() * speed;
         }
-        if input.is_key_pressed(KeyCode::KeyD) {
+        if input.is_action_pressed(Action::MoveRight) {
             self.position += self.direction.cross(self.up).normalize() * speed;
         }
-        if input.is_key_pressed(KeyCode::KeyR) {
+        if input.is_action_pressed(Action::MoveUp) {
             self.position += self.up * speed;
         }
-        if input.is_key_pressed(KeyCode::KeyF) {
+        if input.is_action_pressed(Action::MoveDown) {
             self.position -= self.up * speed;
         }
     }
 
-    fn update_projection(&mut self, args: &RenderInfo) {
+    fn update_zoom(&mut self, args: &RenderInfo) {
         let input = args.input_manager;
-        let fov = self.fov - input.mouse_wheel_delta();
-        self.fov = fov.clamp(1.0, 45.0);
-        let aspect = self.width as f32 / self.height as f32;
-        self.projection_matrix = Mat4::perspective_rh_gl(self.fov.to_radians(), aspect, self.near, self.far);
+        match self.zoom_mode {
+            ZoomMode::Fov => {
+                let fov = self.fov - input.mouse_wheel_delta();
+                self.fov = fov.clamp(1.0, 45.0);
+            }
+            ZoomMode::Dolly => {
+                // One wheel notch moves a tenth of a second at the current camera speed
+                let distance = input.mouse_wheel_delta() * args.ui.camera_speed * 0.1;
+                self.position += self.direction * distance;
+            }
+        }
     }
 }
 
+fn direction_from_angles(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.to_radians().cos() * pitch.to_radians().cos(),
+        pitch.to_radians().sin(),
+        yaw.to_radians().sin() * pitch.to_radians().cos(),
+    )
+    .normalize()
+}
+
 impl Default for Camera {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use crate::input::InputManager;
+    use crate::ui::Ui;
+
+    #[test]
+    fn animation_follows_a_manual_clock() {
+        let (input_manager, ui) = (InputManager::default(), Ui::default());
+        let clock = ManualClock::new();
+        let mut camera = Camera::new();
+        let from = camera.pose();
+        let to = CameraPose {
+            position: from.position + Vec3::new(4.0, 0.0, 0.0),
+            ..from
+        };
+        camera.animate_to(to, Duration::from_secs(1));
+
+        let mut last = clock.now();
+        let mut step = |camera: &mut Camera, dt: Duration| {
+            clock.advance(dt);
+            let now = clock.now();
+            camera.update(&RenderInfo {
+                dt: now - last,
+                time: now,
+                input_manager: &input_manager,
+                ui: &ui,
+            });
+            last = now;
+        };
+
+        step(&mut camera, Duration::from_millis(250));
+        step(&mut camera, Duration::from_millis(250));
+        assert!(camera.position().abs_diff_eq(from.position + Vec3::new(2.0, 0.0, 0.0), 1e-5));
+        assert!(camera.is_animating());
+
+        step(&mut camera, Duration::from_millis(600));
+        assert_eq!(camera.pose(), to);
+        assert!(!camera.is_animating());
+    }
+}
@@ -4,6 +4,7 @@ use std::any::Any;
 pub struct Light {
     pub position: glam::Vec3,
     pub color: [f32; 3],
+    pub specular_color: Option<[f32; 3]>, // Tint of the highlights, `None` uses `color`
     pub intensity: f32,
     inner: Box<dyn LightTrait>,
 }
@@ -14,9 +15,30 @@ trait LightTrait: Any + std::fmt::Debug {
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
+/// How a light's intensity decreases with distance
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Falloff {
+    /// Classic constant/linear/quadratic attenuation, set from a range with `set_range`
+    #[default]
+    Coefficients,
+    /// Physically based, intensity divided by the squared distance. Ignores the coefficients.
+    InverseSquare,
+}
+
+impl Falloff {
+    /// Value of the `falloff` member of the light in the shader
+    pub fn as_uniform(&self) -> i32 {
+        match self {
+            Falloff::Coefficients => 0,
+            Falloff::InverseSquare => 1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PointLight {
     pub attenuation: [f32; 3], // constant, linear, quadratic
+    pub falloff: Falloff,
 }
 
 impl PointLight {
@@ -24,6 +46,11 @@ impl PointLight {
     pub fn new() -> Light {
         Light::new_point_light()
     }
+
+    /// Picks attenuation coefficients that fade the light out at roughly `meters`
+    pub fn set_range(&mut self, meters: f32) {
+        self.attenuation = attenuation_for_range(meters);
+    }
 }
 
 impl LightTrait for PointLight {
@@ -40,6 +67,7 @@ impl Default for PointLight {
     fn default() -> Self {
         Self {
             attenuation: [1.0, 0.09, 0.032],
+            falloff: Falloff::default(),
         }
     }
 }
@@ -48,8 +76,10 @@ impl Default for PointLight {
 pub struct SpotLight {
     pub direction: glam::Vec3,
     pub attenuation: [f32; 3], // constant, linear, quadratic
+    pub falloff: Falloff,
     pub inner_cutoff_rad: f32,
     pub outer_cutoff_rad: f32,
+    pub attached_to_camera: bool, // Follows the camera position and direction, like a flashlight
 }
 
 impl SpotLight {
@@ -57,6 +87,11 @@ impl SpotLight {
     pub fn new() -> Light {
         Light::new_spot_light()
     }
+
+    /// Picks attenuation coefficients that fade the light out at roughly `meters`
+    pub fn set_range(&mut self, meters: f32) {
+        self.attenuation = attenuation_for_range(meters);
+    }
 }
 
 impl LightTrait for SpotLight {
@@ -74,8 +109,10 @@ impl Default for SpotLight {
         Self {
             direction: glam::Vec3::new(0.0, 0.0, -1.0),
             attenuation: [1.0, 0.09, 0.032],
+            falloff: Falloff::default(),
             inner_cutoff_rad: 12.5f32.to_radians(),
             outer_cutoff_rad: 17.5f32.to_radians(),
+            attached_to_camera: false,
         }
     }
 }
@@ -110,6 +147,63 @@ impl Default for DirectionalLight {
     }
 }
 
+/// Rectangular light approximated by a grid of point lights spread over its surface
+#[derive(Debug)]
+pub struct AreaLight {
+    pub width: f32,
+    pub height: f32,
+    pub normal: glam::Vec3,    // Direction the quad faces and lights, the quad lies in the plane perpendicular to it
+    pub attenuation: [f32; 3], // constant, linear, quadratic, applied to each sample
+    pub falloff: Falloff,
+    pub samples: u32,          // Samples per side, the light is split into samples^2 point lights
+}
+
+impl AreaLight {
+    /// World-space positions of the point samples for a quad centered at `center`
+    pub fn sample_positions(&self, center: glam::Vec3) -> Vec<glam::Vec3> {
+        let samples = self.samples.max(1);
+        let (right, up) = self.normal.normalize_or(glam::Vec3::NEG_Y).any_orthonormal_pair();
+        let mut positions = Vec::with_capacity((samples * samples) as usize);
+        for i in 0..samples {
+            for j in 0..samples {
+                // Sample at the center of each cell
+                let u = (i as f32 + 0.5) / samples as f32 - 0.5;
+                let v = (j as f32 + 0.5) / samples as f32 - 0.5;
+                positions.push(center + right * (u * self.width) + up * (v * self.height));
+            }
+        }
+        positions
+    }
+
+    /// Sets the attenuation of each sample so the light fades out at roughly `meters`
+    pub fn set_range(&mut self, meters: f32) {
+        self.attenuation = attenuation_for_range(meters);
+    }
+}
+
+impl LightTrait for AreaLight {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Default for AreaLight {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            height: 1.0,
+            normal: glam::Vec3::NEG_Y,
+            attenuation: [1.0, 0.09, 0.032],
+            falloff: Falloff::default(),
+            samples: 3,
+        }
+    }
+}
+
 impl Light {
     pub fn is_point_light(&self) -> bool {
         self.inner.as_any().is::<PointLight>()
@@ -147,6 +241,40 @@ impl Light {
         self.inner.as_any_mut().downcast_mut::<DirectionalLight>()
     }
 
+    /// Color of the highlights, `specular_color` or `color` when it isn't set
+    pub fn specular(&self) -> [f32; 3] {
+        self.specular_color.unwrap_or(self.color)
+    }
+
+    /// Sets `color` to the tint of a blackbody at `kelvin`, clamped to 1000-12000K.
+    /// Low values are warm (candle ~1900K), 6500K is roughly white daylight.
+    pub fn set_color_temperature(&mut self, kelvin: f32) {
+        self.color = color_temperature_to_rgb(kelvin);
+    }
+
+    /// Falloff of point, spot and area lights, directional lights have none
+    pub fn falloff_mut(&mut self) -> Option<&mut Falloff> {
+        if self.is_point_light() {
+            self.as_point_light_mut().map(|point| &mut point.falloff)
+        } else if self.is_spot_light() {
+            self.as_spot_light_mut().map(|spot| &mut spot.falloff)
+        } else {
+            self.as_area_light_mut().map(|area| &mut area.falloff)
+        }
+    }
+
+    pub fn is_area_light(&self) -> bool {
+        self.inner.as_any().is::<AreaLight>()
+    }
+
+    pub fn as_area_light(&self) -> Option<&AreaLight> {
+        self.inner.as_any().downcast_ref::<AreaLight>()
+    }
+
+    pub fn as_area_light_mut(&mut self) -> Option<&mut AreaLight> {
+        self.inner.as_any_mut().downcast_mut::<AreaLight>()
+    }
+
     pub fn new_point_light() -> Self {
         Self::default()
     }
@@ -164,6 +292,75 @@ impl Light {
             ..Default::default()
         }
     }
+
+    /// Area light facing down, `position` is the center of the quad
+    pub fn new_area_light(width: f32, height: f32) -> Self {
+        Self {
+            inner: Box::new(AreaLight {
+                width,
+                height,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+// Range (meters), linear and quadratic terms from the commonly used Ogre3D table
+const ATTENUATION_TABLE: [(f32, f32, f32); 12] = [
+    (7.0, 0.7, 1.8),
+    (13.0, 0.35, 0.44),
+    (20.0, 0.22, 0.20),
+    (32.0, 0.14, 0.07),
+    (50.0, 0.09, 0.032),
+    (65.0, 0.07, 0.017),
+    (100.0, 0.045, 0.0075),
+    (160.0, 0.027, 0.0028),
+    (200.0, 0.022, 0.0019),
+    (325.0, 0.014, 0.0007),
+    (600.0, 0.007, 0.0002),
+    (3250.0, 0.0014, 0.000007),
+];
+
+// Interpolates the table above, ranges outside of it are clamped
+fn attenuation_for_range(range: f32) -> [f32; 3] {
+    let first = ATTENUATION_TABLE[0];
+    let last = ATTENUATION_TABLE[ATTENUATION_TABLE.len() - 1];
+    if range <= first.0 {
+        return [1.0, first.1, first.2];
+    }
+    if range >= last.0 {
+        return [1.0, last.1, last.2];
+    }
+
+    let upper = ATTENUATION_TABLE.iter().position(|entry| entry.0 >= range).unwrap();
+    let (r0, l0, q0) = ATTENUATION_TABLE[upper - 1];
+    let (r1, l1, q1) = ATTENUATION_TABLE[upper];
+    let t = (range - r0) / (r1 - r0);
+    [1.0, l0 + (l1 - l0) * t, q0 + (q1 - q0) * t]
+}
+
+// Tanner Helland's curve fit of the blackbody color table
+fn color_temperature_to_rgb(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000.0, 12000.0) / 100.0;
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let g = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_16 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+    [r, g, b].map(|c| (c / 255.0).clamp(0.0, 1.0))
 }
 
 impl Default for Light {
@@ -171,6 +368,7 @@ impl Default for Light {
         Light {
             position: glam::Vec3::new(0.0, 0.0, 0.0),
             color: [1.0, 1.0, 1.0],
+            specular_color: None,
             intensity: 1.0,
             inner: Box::new(PointLight::default()),
         }
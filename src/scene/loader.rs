@@ -0,0 +1,468 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::renderer::material::{Material, MaterialProperty};
+use crate::renderer::mesh::{Mesh, PrimitiveMode, Vertex};
+use crate::renderer::shader::ShaderProgram;
+use crate::renderer::texture::{Texture2D, TextureConfig};
+use crate::scene::Object;
+
+/// Loads every mesh of the default scene of a glTF 2.0 file (`.gltf` or `.glb`) as objects
+/// drawn with `shader`, which is expected to be the phong shader (`basic_vertex`/`basic_fragment`).
+///
+/// Each primitive becomes its own object. Base color textures become the diffuse map, tinted
+/// by the base color factor through the `diffuseFactor` uniform, image files that are missing
+/// or broken show the `Texture2D::default_checkerboard` placeholder. The specular map is a flat
+/// gray derived from the roughness factor. Primitives without tangents keep the default from
+/// `Vertex::new`.
+pub fn load_gltf(
+    path: &str,
+    shader: Rc<ShaderProgram>,
+) -> Result<Vec<Rc<RefCell<Object>>>, String> {
+    let gltf = gltf::Gltf::open(path).map_err(|e| e.to_string())?;
+    // Buffers and images are relative to the glTF file
+    let base = Path::new(path).parent().unwrap_or(Path::new("./")).to_path_buf();
+    let buffers =
+        gltf::import_buffers(&gltf.document, Some(&base), gltf.blob).map_err(|e| e.to_string())?;
+    let document = gltf.document;
+    let mut loader = GltfLoader {
+        base,
+        buffers,
+        shader,
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+        objects: Vec::new(),
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or("glTF file has no scene")?;
+    for node in scene.nodes() {
+        loader.load_node(&node, glam::Mat4::IDENTITY)?;
+    }
+
+    Ok(loader.objects)
+}
+
+struct GltfLoader {
+    base: PathBuf,
+    buffers: Vec<gltf::buffer::Data>,
+    shader: Rc<ShaderProgram>,
+    // Shared between nodes instancing the same mesh/material/image
+    meshes: HashMap<(usize, usize), Rc<Mesh>>, // (mesh, primitive)
+    materials: HashMap<Option<usize>, Rc<RefCell<Material>>>, // None is the glTF default material
+    textures: HashMap<usize, Rc<Texture2D>>,
+    objects: Vec<Rc<RefCell<Object>>>,
+}
+
+impl GltfLoader {
+    fn load_node(&mut self, node: &gltf::Node, parent: glam::Mat4) -> Result<(), String> {
+        let transform = parent * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            let (scale, rotation, position) = transform.to_scale_rotation_translation();
+            for primitive in mesh.primitives() {
+                let Some(gl_mesh) = self.load_primitive(&mesh, &primitive)? else {
+                    continue;
+                };
+                let material = self.load_material(&primitive.material())?;
+
+                let mut object = Object::new(gl_mesh, material);
+                object.transform.position = position;
+                object.transform.rotation = rotation;
+                object.transform.scale = scale;
+                self.objects.push(Rc::new(RefCell::new(object)));
+            }
+        }
+
+        for child in node.children() {
+            self.load_node(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    // Returns `None` for primitives using a topology meshes can't draw
+    fn load_primitive(
+        &mut self,
+        mesh: &gltf::Mesh,
+        primitive: &gltf::Primitive,
+    ) -> Result<Option<Rc<Mesh>>, String> {
+        let key = (mesh.index(), primitive.index());
+        if let Some(mesh) = self.meshes.get(&key) {
+            return Ok(Some(Rc::clone(mesh)));
+        }
+
+        let mode = match primitive.mode() {
+            gltf::mesh::Mode::Triangles => PrimitiveMode::Triangles,
+            gltf::mesh::Mode::TriangleStrip => PrimitiveMode::TriangleStrip,
+            gltf::mesh::Mode::Lines => PrimitiveMode::Lines,
+            gltf::mesh::Mode::LineStrip => PrimitiveMode::LineStrip,
+            gltf::mesh::Mode::Points => PrimitiveMode::Points,
+            mode => {
+                println!("Skipping glTF primitive with unsupported mode {:?}", mode);
+                return Ok(None);
+            }
+        };
+
+        let vertices = read_vertices(primitive, &self.buffers)?;
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let indices: Option<Vec<u32>> = reader
+            .read_indices()
+            .map(|indices| indices.into_u32().collect());
+
+        let mut gl_mesh = Mesh::new();
+        gl_mesh.init(&vertices, indices.as_deref());
+        gl_mesh.set_primitive_mode(mode);
+        let gl_mesh = Rc::new(gl_mesh);
+        self.meshes.insert(key, Rc::clone(&gl_mesh));
+        Ok(Some(gl_mesh))
+    }
+
+    fn load_material(
+        &mut self,
+        material: &gltf::Material,
+    ) -> Result<Rc<RefCell<Material>>, String> {
+        if let Some(loaded) = self.materials.get(&material.index()) {
+            return Ok(Rc::clone(loaded));
+        }
+
+        // The factor is linear and multiplies the texture, it's applied in the shader so it
+        // isn't quantized or decoded as sRGB
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let diffuse = match pbr.base_color_texture() {
+            Some(info) => self.load_texture(&info.texture().source())?,
+            None => solid_texture(&[1.0; 3], &TextureConfig::srgb())?,
+        };
+
+        // Rough approximation of the metallic-roughness model with phong parameters
+        let roughness = pbr.roughness_factor();
+        let specular = solid_texture(&[1.0 - roughness], &TextureConfig::grayscale())?;
+        let shininess = ((1.0 - roughness) * 128.0).max(1.0) as i32;
+
+        let name = material.name().unwrap_or("gltf_material");
+        let loaded = Rc::new(RefCell::new(Material::new_with_properties(
+            name,
+            Rc::clone(&self.shader),
+            [
+                (
+                    "material.diffuse".to_string(),
+                    MaterialProperty::Texture(diffuse),
+                ),
+                (
+                    "diffuseFactor".to_string(),
+                    MaterialProperty::Vec3([r, g, b]),
+                ),
+                (
+                    "material.specular".to_string(),
+                    MaterialProperty::Texture(specular),
+                ),
+                (
+                    "material.shininess".to_string(),
+                    MaterialProperty::Integer(shininess),
+                ),
+                (
+                    "material.uv_tiling".to_string(),
+                    MaterialProperty::Vec2([1.0, 1.0]),
+                ),
+                (
+                    "material.uv_offset".to_string(),
+                    MaterialProperty::Vec2([0.0, 0.0]),
+                ),
+            ]
+            .into(),
+        )));
+        self.materials.insert(material.index(), Rc::clone(&loaded));
+        Ok(loaded)
+    }
+
+    // Base color textures are the only ones imported, so they are always sRGB
+    fn load_texture(&mut self, image: &gltf::Image) -> Result<Rc<Texture2D>, String> {
+        if let Some(texture) = self.textures.get(&image.index()) {
+            return Ok(Rc::clone(texture));
+        }
+
+        // glTF UVs start at the top of the image, which matches uploading rows top first
+        let config = TextureConfig {
+            flip_vertically: false,
+            ..TextureConfig::srgb()
+        };
+        // Plain relative paths are loaded like any other texture file, data URIs and images
+        // stored in buffers are decoded by the gltf crate
+        if let gltf::image::Source::Uri { uri, .. } = image.source()
+            && !uri.contains(':')
+            && !uri.contains('%')
+        {
+            let path = self.base.join(uri);
+            let texture = Rc::new(Texture2D::new_from_file_or_default(
+                &path.to_string_lossy(),
+                &config,
+            ));
+            self.textures.insert(image.index(), Rc::clone(&texture));
+            return Ok(texture);
+        }
+
+        let image_index = image.index();
+        let image = gltf::image::Data::from_source(image.source(), Some(&self.base), &self.buffers)
+            .map_err(|e| e.to_string())?;
+        let stride = match image.format {
+            gltf::image::Format::R8 => 1,
+            gltf::image::Format::R8G8 => 2,
+            gltf::image::Format::R8G8B8 => 3,
+            gltf::image::Format::R8G8B8A8 => 4,
+            format => return Err(format!("Unsupported glTF image format {:?}", format)),
+        };
+        // Expand to RGB8, gray images are replicated and alpha is dropped
+        let pixels: Vec<u8> = image
+            .pixels
+            .chunks_exact(stride)
+            .flat_map(|pixel| match stride {
+                1 | 2 => [pixel[0]; 3],
+                _ => [pixel[0], pixel[1], pixel[2]],
+            })
+            .collect();
+
+        let texture = Rc::new(Texture2D::new_from_pixels(
+            image.width,
+            image.height,
+            &pixels,
+            &config,
+        )?);
+        self.textures.insert(image_index, Rc::clone(&texture));
+        Ok(texture)
+    }
+}
+
+// 1x1 texture with a constant value in [0, 1], one component per channel of `config`
+fn solid_texture(value: &[f32], config: &TextureConfig) -> Result<Rc<Texture2D>, String> {
+    let pixel: Vec<u8> = value
+        .iter()
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect();
+    let config = TextureConfig {
+        mipmaps: false,
+        ..*config
+    };
+    Ok(Rc::new(Texture2D::new_from_pixels(1, 1, &pixel, &config)?))
+}
+
+// Vertex attributes of a primitive, missing attributes get the defaults of `Vertex::new`
+fn read_vertices(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<Vertex>, String> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or("glTF primitive has no positions")?
+        .collect();
+    let mut normals = reader.read_normals();
+    let mut uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32());
+    let mut uvs1 = reader.read_tex_coords(1).map(|uvs| uvs.into_f32());
+    let mut colors = reader.read_colors(0).map(|colors| colors.into_rgba_f32());
+    let mut tangents = reader.read_tangents();
+    Ok(positions
+        .into_iter()
+        .map(|position| {
+            let normal = normals
+                .as_mut()
+                .and_then(|n| n.next())
+                .unwrap_or([0.0, 1.0, 0.0]);
+            let uv = uvs.as_mut().and_then(|uv| uv.next()).unwrap_or([0.0, 0.0]);
+            let uv1 = uvs1.as_mut().and_then(|uv| uv.next()).unwrap_or(uv);
+            let color = colors.as_mut().and_then(|c| c.next()).unwrap_or([1.0; 4]);
+            let vertex = Vertex::new(position, normal, uv)
+                .with_color(color)
+                .with_uv1(uv1);
+            match tangents.as_mut().and_then(|t| t.next()) {
+                Some(tangent) => vertex.with_tangent(tangent),
+                None => vertex,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    // One triangle whose material has a base color factor, and a base color texture from the
+    // file `image` if given
+    fn write_triangle_gltf(dir: &Path, base_color: [f32; 4], image: Option<&str>) -> String {
+        write_gltf(dir, base_color, image, None)
+    }
+
+    // Same triangle, with `tangent` on every vertex if given
+    fn write_gltf(
+        dir: &Path,
+        base_color: [f32; 4],
+        image: Option<&str>,
+        tangent: Option<[f32; 4]>,
+    ) -> String {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut bytes: Vec<u8> = positions.iter().flat_map(|p| p.to_le_bytes()).collect();
+        let positions_length = bytes.len();
+        if let Some(tangent) = tangent {
+            bytes.extend(tangent.repeat(3).iter().flat_map(|t| t.to_le_bytes()));
+        }
+        std::fs::write(dir.join("triangle.bin"), &bytes).unwrap();
+        let gltf = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0 }}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0{} }}, "material": 0 }}] }}],
+                "materials": [{{ "pbrMetallicRoughness": {{ "baseColorFactor": {:?}{} }} }}],{}
+                "buffers": [{{ "uri": "triangle.bin", "byteLength": {} }}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": {} }}{}],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                }}{}]
+            }}"#,
+            tangent.map_or("", |_| r#", "TANGENT": 1"#),
+            base_color,
+            image.map_or("", |_| r#", "baseColorTexture": { "index": 0 }"#),
+            image.map_or(String::new(), |uri| format!(
+                r#""textures": [{{ "source": 0 }}], "images": [{{ "uri": "{}" }}],"#,
+                uri
+            )),
+            bytes.len(),
+            positions_length,
+            tangent.map_or(String::new(), |_| format!(
+                r#", {{ "buffer": 0, "byteOffset": {}, "byteLength": {} }}"#,
+                positions_length,
+                bytes.len() - positions_length
+            )),
+            tangent.map_or(
+                "",
+                |_| r#", { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC4" }"#
+            )
+        );
+        let path = dir.join("triangle.gltf");
+        std::fs::write(&path, gltf).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn base_color_factor_is_kept_linear() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("gltf_factor_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_triangle_gltf(&dir, [0.5, 0.25, 1.0, 1.0], None);
+        let shader = Rc::new(
+            ShaderProgram::from_files("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+                .unwrap(),
+        );
+
+        let objects = load_gltf(&path, shader).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(objects.len(), 1);
+        let material = objects[0].borrow().material();
+        let material = material.borrow();
+        assert!(matches!(
+            material.properties().get("diffuseFactor"),
+            Some(MaterialProperty::Vec3([0.5, 0.25, 1.0]))
+        ));
+        // The factor isn't baked into the texture as well
+        assert!(matches!(
+            material.properties().get("material.diffuse"),
+            Some(MaterialProperty::Texture(_))
+        ));
+    }
+
+    // Base level of the diffuse texture of the first object's material, as RGB rows
+    fn diffuse_pixels(objects: &[Rc<RefCell<Object>>], width: usize, height: usize) -> Vec<u8> {
+        let material = objects[0].borrow().material();
+        let Some(MaterialProperty::Texture(texture)) =
+            material.borrow().properties().get("material.diffuse").cloned()
+        else {
+            panic!("No diffuse texture");
+        };
+        let mut pixels = vec![0u8; width * height * 3];
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.id());
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+        pixels
+    }
+
+    #[test]
+    fn image_files_are_not_flipped() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("gltf_image_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Red top row, blue bottom row
+        image::RgbImage::from_raw(1, 2, vec![255, 0, 0, 0, 0, 255])
+            .unwrap()
+            .save(dir.join("rows.png"))
+            .unwrap();
+        let path = write_triangle_gltf(&dir, [1.0; 4], Some("rows.png"));
+        let shader = Rc::new(
+            ShaderProgram::from_files("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+                .unwrap(),
+        );
+
+        let objects = load_gltf(&path, shader);
+        std::fs::remove_dir_all(&dir).unwrap();
+        // glTF puts v = 0 at the top of the image, which is the first row uploaded
+        assert_eq!(diffuse_pixels(&objects.unwrap(), 1, 2), [255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn missing_image_files_get_a_placeholder() {
+        let Some(_context) = test_support::context() else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("gltf_missing_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_triangle_gltf(&dir, [1.0; 4], Some("missing.png"));
+        let shader = Rc::new(
+            ShaderProgram::from_files("./shaders/basic_vertex.vs", "./shaders/basic_fragment.fs")
+                .unwrap(),
+        );
+
+        let objects = load_gltf(&path, shader);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let objects = objects.unwrap();
+        assert_eq!(objects.len(), 1);
+        // The magenta corner of the checkerboard
+        assert_eq!(diffuse_pixels(&objects, 64, 64)[..3], [255, 0, 255]);
+    }
+
+    #[test]
+    fn tangents_are_imported() {
+        let dir = std::env::temp_dir().join(format!("gltf_tangents_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let with_tangents = write_gltf(&dir, [1.0; 4], None, Some([0.0, 0.0, 1.0, -1.0]));
+        let (document, buffers, _) = gltf::import(&with_tangents).unwrap();
+        let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+        let vertices = read_vertices(&primitive, &buffers).unwrap();
+        assert!(vertices.iter().all(|v| v.tangent() == [0.0, 0.0, 1.0, -1.0]));
+
+        let without_tangents = write_triangle_gltf(&dir, [1.0; 4], None);
+        let (document, buffers, _) = gltf::import(&without_tangents).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+        let vertices = read_vertices(&primitive, &buffers).unwrap();
+        assert_eq!(vertices[0].tangent(), [1.0, 0.0, 0.0, 1.0]);
+    }
+}
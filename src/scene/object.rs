@@ -1,10 +1,16 @@
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use crate::renderer::mesh::Mesh;
 use crate::renderer::material::{Material, PropertiesMap};
+use crate::renderer::shader::ShaderProgram;
 
-#[derive(Debug)]
+thread_local! {
+    // Id given to the next object, 0 is left for "no object" in the picking pass
+    static NEXT_OBJECT_ID: Cell<u32> = const { Cell::new(1) };
+}
+
+#[derive(Debug, Clone)]
 pub struct Transform {
     pub position: glam::Vec3,
     pub scale: glam::Vec3,
@@ -23,6 +29,52 @@ impl Transform {
     pub fn model_matrix(&self) -> glam::Mat4 {
         glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
     }
+
+    /// Blends towards `other`, `t` = 0 gives `self` and 1 gives `other`
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            position: self.position.lerp(other.position, t),
+            scale: self.scale.lerp(other.scale, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
+
+    pub fn translate(&mut self, delta: glam::Vec3) {
+        self.position += delta;
+    }
+
+    /// Turns by `yaw` around Y, then `pitch` around the turned X axis, then `roll` around the
+    /// resulting Z axis (intrinsic YXZ), in radians. The combined turn is applied on top of the
+    /// current rotation, so it's relative to the world axes.
+    pub fn rotate_euler(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        let rotation = glam::Quat::from_euler(glam::EulerRot::YXZ, yaw, pitch, roll);
+        self.rotation = (rotation * self.rotation).normalize();
+    }
+
+    /// Turns the object so its -Z axis points at `target`, with its Y axis as close to `up` as
+    /// possible. Nothing changes when `target` is the object's position.
+    pub fn look_at(&mut self, target: glam::Vec3, up: glam::Vec3) {
+        let Some(forward) = (target - self.position).try_normalize() else {
+            return;
+        };
+        let back = -forward;
+        // Any perpendicular axis will do when looking straight along `up`
+        let right = up
+            .cross(back)
+            .try_normalize()
+            .unwrap_or_else(|| back.any_orthonormal_vector());
+        let up = back.cross(right);
+        self.rotation = glam::Quat::from_mat3(&glam::Mat3::from_cols(right, up, back));
+    }
+
+    pub fn set_uniform_scale(&mut self, scale: f32) {
+        self.scale = glam::Vec3::splat(scale);
+    }
+
+    /// Inverse-transpose of the model matrix, keeps normals perpendicular under non-uniform scaling
+    pub fn normal_matrix(&self) -> glam::Mat3 {
+        glam::Mat3::from_mat4(self.model_matrix()).inverse().transpose()
+    }
 }
 
 impl Default for Transform {
@@ -31,29 +83,223 @@ impl Default for Transform {
     }
 }
 
+pub type AnimationFn = dyn Fn(&mut Transform, f32);
+
+/// Built-in animations evaluated every frame from the elapsed time, in seconds
+pub enum Animator {
+    /// Rotates around `axis` at `speed` radians per second
+    Spin { axis: glam::Vec3, speed: f32 },
+    /// Moves up and down around `base` with a sine wave
+    Bob {
+        base: glam::Vec3,
+        amplitude: f32,
+        frequency: f32, // Hz
+    },
+    /// Circles around `center` on the XZ plane at `speed` radians per second
+    Orbit {
+        center: glam::Vec3,
+        radius: f32,
+        speed: f32,
+    },
+    Custom(Box<AnimationFn>),
+}
+
+impl Animator {
+    pub fn spin(axis: glam::Vec3, speed: f32) -> Self {
+        Self::Spin {
+            axis: axis.normalize(),
+            speed,
+        }
+    }
+
+    pub fn apply(&self, transform: &mut Transform, time: f32) {
+        match self {
+            Animator::Spin { axis, speed } => {
+                transform.rotation = glam::Quat::from_axis_angle(*axis, time * speed);
+            }
+            Animator::Bob {
+                base,
+                amplitude,
+                frequency,
+            } => {
+                let offset = (time * frequency * std::f32::consts::TAU).sin() * amplitude;
+                transform.position = *base + glam::Vec3::Y * offset;
+            }
+            Animator::Orbit {
+                center,
+                radius,
+                speed,
+            } => {
+                let angle = time * speed;
+                transform.position =
+                    *center + glam::vec3(angle.cos() * radius, 0.0, angle.sin() * radius);
+            }
+            Animator::Custom(f) => f(transform, time),
+        }
+    }
+}
+
 pub struct Object {
     pub transform: Transform,
-    pub rotate: bool,
+    pub animator: Option<Animator>,
     pub material_overrides: PropertiesMap,
+    pub visible: bool, // Hidden objects keep animating but aren't drawn
     material: Rc<RefCell<Material>>,
     mesh: Rc<Mesh>,
+    steps: Option<(Transform, Transform)>, // Previous and latest fixed update of the animator
+    id: u32, // Unique among all objects, written to the picking target
 }
 
 impl Object {
     pub fn new(mesh: Rc<Mesh>, material: Rc<RefCell<Material>>) -> Self {
         Self {
             transform: Transform::default(),
-            rotate: false,
+            animator: None,
             material_overrides: PropertiesMap::new(),
+            visible: true,
             material,
             mesh,
+            steps: None,
+            id: NEXT_OBJECT_ID.replace(NEXT_OBJECT_ID.get() + 1),
+        }
+    }
+
+    /// Unique id of the object, never 0
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Advances the attached animator, if any, to `time` seconds
+    pub fn animate(&mut self, time: f32) {
+        if let Some(animator) = &self.animator {
+            animator.apply(&mut self.transform, time);
         }
     }
 
+    /// Runs the animator for a fixed update at `time` seconds, the transform itself only
+    /// changes in `interpolate`
+    pub fn fixed_update(&mut self, time: f32) {
+        let Some(animator) = &self.animator else {
+            return;
+        };
+        let latest = match self.steps.take() {
+            Some((_, latest)) => latest,
+            None => self.transform.clone(),
+        };
+        let mut next = latest.clone();
+        animator.apply(&mut next, time);
+        self.steps = Some((latest, next));
+    }
+
+    /// Places the object `alpha` of the way between its last two fixed updates
+    pub fn interpolate(&mut self, alpha: f32) {
+        if let Some((previous, latest)) = &self.steps {
+            self.transform = previous.lerp(latest, alpha);
+        }
+    }
+
+    pub fn triangle_count(&self) -> u32 {
+        self.mesh.triangle_count()
+    }
+
+    pub fn material(&self) -> Rc<RefCell<Material>> {
+        Rc::clone(&self.material)
+    }
+
+    pub fn mesh(&self) -> Rc<Mesh> {
+        Rc::clone(&self.mesh)
+    }
+
     pub fn render(&self) {
-        let material = self.material.borrow();
-        material.use_material(&self.material_overrides);
-        material.shader().set_uniform_mat4("model", &self.transform.model_matrix());
+        self.material
+            .try_borrow()
+            .expect("Material mutably borrowed while rendering")
+            .bind();
+        self.render_instance();
+    }
+
+    /// Draws the object with its material already bound, e.g. by the previous object sharing it
+    pub fn render_instance(&self) {
+        self.material
+            .try_borrow()
+            .expect("Material mutably borrowed while rendering")
+            .apply_instance(&self.material_overrides, &self.transform.model_matrix());
         self.mesh.draw();
     }
+
+    /// Draws the mesh with an already bound `shader` instead of the object's material
+    pub fn draw_with(&self, shader: &ShaderProgram, model: &glam::Mat4) {
+        shader.invalidate_uniform("model");
+        shader.set_uniform_mat4("model", model);
+        self.mesh.draw();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Scene;
+    use crate::test_support;
+
+    fn forward(transform: &Transform) -> glam::Vec3 {
+        transform.rotation * glam::Vec3::NEG_Z
+    }
+
+    #[test]
+    fn look_at_points_forward_at_the_target() {
+        let position = glam::vec3(1.0, 2.0, 3.0);
+        for target in [
+            glam::vec3(4.0, 2.0, 3.0),
+            glam::vec3(-2.0, 0.5, 7.0),
+            glam::vec3(1.0, 2.0, -10.0),
+            // Straight above and below, along `up`
+            glam::vec3(1.0, 12.0, 3.0),
+            glam::vec3(1.0, -5.0, 3.0),
+        ] {
+            let mut transform = Transform::new(position, glam::Vec3::ONE, glam::Quat::IDENTITY);
+            transform.look_at(target, glam::Vec3::Y);
+            let expected = (target - position).normalize();
+            assert!(
+                forward(&transform).abs_diff_eq(expected, 1e-5),
+                "Looking at {} faces {}",
+                target,
+                forward(&transform)
+            );
+            assert!(transform.rotation.is_normalized());
+        }
+    }
+
+    #[test]
+    fn look_at_keeps_the_rotation_when_the_target_is_the_position() {
+        let rotation = glam::Quat::from_rotation_y(1.0);
+        let mut transform = Transform::new(glam::Vec3::ONE, glam::Vec3::ONE, rotation);
+        transform.look_at(glam::Vec3::ONE, glam::Vec3::Y);
+        assert_eq!(transform.rotation, rotation);
+    }
+
+    #[test]
+    fn objects_sharing_a_material_keep_their_own_model_and_overrides() {
+        let Some(mut headless) = test_support::context() else {
+            return;
+        };
+        let mut scene = Scene::new();
+        scene.register_mesh("quad", test_support::quad_mesh());
+        let material = Material::unlit(test_support::unlit_shader(), [1.0, 1.0, 1.0]);
+        scene.register_material("unlit", Rc::new(RefCell::new(material)));
+        // Red on the left, green on the right, then red again so the override alternates
+        for (x, color) in [(-1.2, [1.0, 0.0, 0.0]), (1.2, [0.0, 1.0, 0.0]), (0.0, [1.0, 0.0, 0.0])] {
+            let object = scene.spawn("quad", "unlit").unwrap();
+            let mut object = object.borrow_mut();
+            object.transform.position = glam::vec3(x, 0.0, -2.0);
+            object.material_overrides.set_vec3("color", color);
+        }
+
+        let image = headless
+            .render_to_image(&scene, &test_support::camera(64, 32), 64, 32)
+            .unwrap();
+        let [left, middle, right] = [16, 32, 48].map(|x| image.get_pixel(x, 16).0);
+        assert_eq!(left, [255, 0, 0]);
+        assert_eq!(middle, [255, 0, 0]);
+        assert_eq!(right, [0, 255, 0]);
+    }
 }
@@ -0,0 +1,51 @@
+//! Helpers shared by the tests that need a GL context
+
+use std::rc::Rc;
+
+use crate::headless::Headless;
+use crate::renderer::mesh::{Mesh, Vertex};
+use crate::renderer::shader::ShaderProgram;
+use crate::scene::Camera;
+
+/// Headless context for a test, the context must stay alive while GL objects are used.
+/// Panics without a usable GPU device, unless `OPENGL_RUST_SKIP_GL_TESTS` is set. In that
+/// case this returns `None` and the test returns early.
+pub fn context() -> Option<Headless> {
+    match Headless::new() {
+        Ok(headless) => Some(headless),
+        Err(e) if std::env::var_os("OPENGL_RUST_SKIP_GL_TESTS").is_some() => {
+            println!("Skipping, no headless GL context: {}", e);
+            None
+        }
+        Err(e) => panic!(
+            "No headless GL context: {}, set OPENGL_RUST_SKIP_GL_TESTS to skip the GL tests",
+            e
+        ),
+    }
+}
+
+/// Unit quad in the XY plane facing +Z
+pub fn quad_mesh() -> Rc<Mesh> {
+    let normal = [0.0, 0.0, 1.0];
+    let vertices = [
+        Vertex::new([-0.5, -0.5, 0.0], normal, [0.0, 0.0]),
+        Vertex::new([0.5, -0.5, 0.0], normal, [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.0], normal, [1.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], normal, [0.0, 1.0]),
+    ];
+    let mut mesh = Mesh::new();
+    mesh.init(&vertices, Some(&[0, 1, 2, 2, 3, 0]));
+    Rc::new(mesh)
+}
+
+pub fn unlit_shader() -> Rc<ShaderProgram> {
+    Rc::new(ShaderProgram::from_files("./shaders/unlit.vs", "./shaders/unlit.fs").unwrap())
+}
+
+/// Default camera, at (0, 0, 1) looking down -Z, with the aspect of a `width` x `height` image
+pub fn camera(width: u32, height: u32) -> Camera {
+    let mut camera = Camera::new();
+    camera.resize(width, height);
+    camera.update_matrices();
+    camera
+}
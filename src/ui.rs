@@ -1,15 +1,115 @@
+use std::time::Duration;
+
 use egui::Context;
+use winit::event::MouseButton;
+
+use crate::input::{Action, ActionMap, InputManager};
+use crate::renderer::material::{linear_to_srgb, srgb_to_linear, MaterialProperty};
+use crate::renderer::{
+    Capabilities, DebugMode, DepthFunc, FrontFace, LightCounts, PolygonOffset, RenderStats, ToneMapping,
+    MAX_DIRECTIONAL_LIGHTS, MAX_SPOT_LIGHTS,
+};
+use crate::scene::{Camera, CameraPose, Falloff, Light, Scene, ZoomMode};
+
+/// Number of camera bookmarks, recalled with the number keys 1 to 9
+pub const BOOKMARK_SLOTS: usize = 9;
+const BOOKMARK_TRANSITION: Duration = Duration::from_millis(600);
+
+/// Window corner the statistics overlay is anchored to
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    fn as_align(&self) -> egui::Align2 {
+        match self {
+            Corner::TopLeft => egui::Align2::LEFT_TOP,
+            Corner::TopRight => egui::Align2::RIGHT_TOP,
+            Corner::BottomLeft => egui::Align2::LEFT_BOTTOM,
+            Corner::BottomRight => egui::Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+/// How the right mouse button turns on mouse-look
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MouseLookMode {
+    #[default]
+    Hold,   // Looks around while the button is held
+    Toggle, // Each click turns it on or off
+}
 
 pub struct Ui {
     pub quit: bool,
     pub camera_speed: f32,
     pub clear_color: [f32; 3],
+    pub gradient_background: bool, // Draw a vertical gradient instead of the clear color
+    pub background_top: [f32; 3],
+    pub background_bottom: [f32; 3],
     pub camera_sensitivity: f32,
+    pub mouse_look_mode: MouseLookMode,
+    pub mouse_look_toggled: bool, // Toggle mode only, flipped by the app on right clicks
     pub light_color: [f32; 3],
     pub shininess: i32,
-    pub ambient_strength: f32,
+    pub ambient_strength: f32, // Intensity of the scene's ambient light, in [0, 1], as last shown
+    pub ambient_color: [f32; 3],
     pub specular_strength: f32,
     pub fps: u32,
+    pub frame_time_ms: f32,
+    pub paused: bool,         // Animation time is frozen
+    pub step_requested: bool, // Advance one step while paused, cleared by the app
+    pub cursor_grabbed: bool, // Set by the app every frame
+    pub cursor_grab_toggle_requested: bool, // Cleared by the app
+    pub gpu_timings: Vec<(&'static str, f32)>, // Milliseconds per renderer section
+    pub render_stats: RenderStats, // Set by the app every frame
+    pub light_counts: LightCounts, // Set by the app every frame
+    pub show_stats_overlay: bool,  // Toggled with F3
+    pub stats_overlay_corner: Corner,
+    pub display_scale: f32, // Window scale factor reported by winit
+    pub ui_scale: f32,      // Manual multiplier on top of the display scale
+    pub capabilities: Option<Capabilities>, // Set by the app once the renderer is created
+    pub scene_names: Vec<String>,           // Set by the app once at startup
+    pub active_scene: usize,
+    pub scene_switch_requested: Option<usize>, // Cleared by the app
+    pub show_grid: bool,
+    pub smooth_lines: bool, // Antialiased grid and wireframe lines
+    pub grid_spacing: f32,
+    pub grid_extent: f32,
+    pub hdr: bool,
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    pub bloom: bool,
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub depth_test: bool,
+    pub depth_func: DepthFunc,
+    pub front_face: FrontFace,
+    pub face_culling: bool,
+    pub depth_prepass: bool,
+    pub debug_mode: DebugMode,
+    pub polygon_offset: bool,
+    pub polygon_offset_factor: f32,
+    pub polygon_offset_units: f32,
+    pub split_view: bool,
+    pub selected_object: Option<usize>,
+    pub picking: bool, // Left clicks select the object under the cursor
+    pub object_count: usize,
+    pub outline_color: [f32; 3],
+    pub outline_width: f32,
+    pub wireframe_color: [f32; 3],
+    pub wireframe_width: f32, // In pixels, clamped to what the driver supports
+    pub camera_bookmarks: [Option<CameraPose>; BOOKMARK_SLOTS],
+    pub smooth_bookmarks: bool, // Fly to recalled bookmarks instead of jumping
+    pub key_bindings: ActionMap,        // Set by the app every frame
+    pub rebinding: Option<Action>,      // Waiting for a key press, cleared by the app
+    pub binding_error: Option<String>,  // Why the last rebind was refused
+    light_temperatures: Vec<f32>, // Last Kelvin value picked for each light in the editor
+    light_ranges: Vec<f32>,       // Last range in meters picked for each light in the editor
 }
 
 impl Ui {
@@ -18,30 +118,221 @@ impl Ui {
             quit: false,
             camera_speed: 5.0,
             clear_color: [0.0, 0.0, 0.0],
+            gradient_background: false,
+            background_top: [0.25, 0.35, 0.55],
+            background_bottom: [0.05, 0.05, 0.08],
             camera_sensitivity: 0.4,
+            mouse_look_mode: MouseLookMode::default(),
+            mouse_look_toggled: false,
             light_color: [1.0, 1.0, 1.0],
             shininess: 32,
             ambient_strength: 0.1,
+            ambient_color: [1.0, 1.0, 1.0],
             specular_strength: 0.5,
             fps: 0,
+            frame_time_ms: 0.0,
+            paused: false,
+            step_requested: false,
+            cursor_grabbed: false,
+            cursor_grab_toggle_requested: false,
+            gpu_timings: Vec::new(),
+            render_stats: RenderStats::default(),
+            light_counts: LightCounts::default(),
+            show_stats_overlay: false,
+            stats_overlay_corner: Corner::default(),
+            display_scale: 1.0,
+            ui_scale: 1.0,
+            capabilities: None,
+            scene_names: Vec::new(),
+            active_scene: 0,
+            scene_switch_requested: None,
+            show_grid: false,
+            smooth_lines: false,
+            grid_spacing: 1.0,
+            grid_extent: 20.0,
+            hdr: true,
+            exposure: 1.0,
+            tone_mapping: ToneMapping::default(),
+            bloom: false,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+            depth_test: true,
+            depth_func: DepthFunc::default(),
+            front_face: FrontFace::default(),
+            face_culling: false,
+            depth_prepass: false,
+            debug_mode: DebugMode::default(),
+            polygon_offset: false,
+            polygon_offset_factor: 1.0,
+            polygon_offset_units: 1.0,
+            split_view: false,
+            selected_object: None,
+            picking: false,
+            object_count: 0,
+            outline_color: [1.0, 0.6, 0.0],
+            outline_width: 0.05,
+            wireframe_color: [0.0, 1.0, 0.0],
+            wireframe_width: 1.0,
+            camera_bookmarks: [None; BOOKMARK_SLOTS],
+            smooth_bookmarks: true,
+            key_bindings: ActionMap::new(),
+            rebinding: None,
+            binding_error: None,
+            light_temperatures: Vec::new(),
+            light_ranges: Vec::new(),
         }
     }
 
-    pub fn run(&mut self, ctx: &Context) {
+    pub fn run(&mut self, ctx: &Context, scene: &mut Scene) {
+        if self.show_stats_overlay {
+            self.stats_overlay(ctx);
+        }
+
         egui::Window::new("Controls")
             .collapsible(false)
             .show(ctx, |ui| {
+                if self.scene_names.len() > 1 {
+                    let mut selected = self.active_scene;
+                    egui::ComboBox::from_label("Scene (F4)")
+                        .selected_text(&self.scene_names[self.active_scene])
+                        .show_ui(ui, |ui| {
+                            for (i, name) in self.scene_names.iter().enumerate() {
+                                ui.selectable_value(&mut selected, i, name);
+                            }
+                        });
+                    if selected != self.active_scene {
+                        self.scene_switch_requested = Some(selected);
+                    }
+                }
                 ui.label(format!("FPS: {}", self.fps));
+                ui.label(format!("Frame time: {:.2} ms (CPU)", self.frame_time_ms));
+                for (section, ms) in &self.gpu_timings {
+                    ui.label(format!("GPU {}: {:.2} ms", section, ms));
+                }
+                ui.label(format!(
+                    "Draw calls: {}, triangles: {}, buffer uploads: {}",
+                    self.render_stats.draw_calls,
+                    self.render_stats.triangles,
+                    self.render_stats.buffer_uploads
+                ));
+                ui.label(format!(
+                    "Lights: {} point, {}/{} spot, {}/{} directional",
+                    self.light_counts.point,
+                    self.light_counts.spot,
+                    MAX_SPOT_LIGHTS,
+                    self.light_counts.directional,
+                    MAX_DIRECTIONAL_LIGHTS
+                ));
+                if self.light_counts.dropped() > 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "{} spot and {} directional lights over the cap are ignored",
+                            self.light_counts.dropped_spot, self.light_counts.dropped_directional
+                        ),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_stats_overlay, "Overlay (F3)");
+                    egui::ComboBox::from_id_salt("stats_overlay_corner")
+                        .selected_text(format!("{:?}", self.stats_overlay_corner))
+                        .show_ui(ui, |ui| {
+                            for corner in [
+                                Corner::TopLeft,
+                                Corner::TopRight,
+                                Corner::BottomLeft,
+                                Corner::BottomRight,
+                            ] {
+                                ui.selectable_value(&mut self.stats_overlay_corner, corner, format!("{:?}", corner));
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.add(egui::Slider::new(&mut self.ui_scale, 0.5..=3.0).text("UI scale"));
+                    if ui.button("Reset").clicked() {
+                        self.ui_scale = 1.0;
+                    }
+                });
+                ui.label(format!("Display scale: {:.2}", self.display_scale));
+                ui.horizontal(|ui| {
+                    let label = if self.paused { "Resume (P)" } else { "Pause (P)" };
+                    if ui.button(label).clicked() {
+                        self.paused = !self.paused;
+                    }
+                    if ui
+                        .add_enabled(self.paused, egui::Button::new("Step (.)"))
+                        .clicked()
+                    {
+                        self.step_requested = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let state = if self.cursor_grabbed { "grabbed" } else { "free" };
+                    ui.label(format!("Cursor: {}", state));
+                    let label = if self.cursor_grabbed { "Release (Alt)" } else { "Grab (Alt)" };
+                    if ui.button(label).clicked() {
+                        self.cursor_grab_toggle_requested = true;
+                    }
+                });
+                if scene.cameras().len() > 1 {
+                    let mut active = scene.active_camera();
+                    egui::ComboBox::from_label("Camera")
+                        .selected_text(format!("Camera {}", active))
+                        .show_ui(ui, |ui| {
+                            for i in 0..scene.cameras().len() {
+                                ui.selectable_value(&mut active, i, format!("Camera {}", i));
+                            }
+                        });
+                    if active != scene.active_camera() {
+                        scene.set_active_camera(active).unwrap();
+                    }
+                }
                 ui.add(egui::Slider::new(&mut self.camera_speed, 1.0..=20.0).text("Camera speed"));
+                let mut zoom_mode = scene.camera().zoom_mode();
+                egui::ComboBox::from_label("Scroll zoom")
+                    .selected_text(format!("{:?}", zoom_mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut zoom_mode, ZoomMode::Fov, "Fov");
+                        ui.selectable_value(&mut zoom_mode, ZoomMode::Dolly, "Dolly");
+                    });
+                scene.camera_mut().set_zoom_mode(zoom_mode);
                 ui.add(
                     egui::Slider::new(&mut self.camera_sensitivity, 0.1..=1.0)
                         .text("Camera sensitivity"),
                 );
+                let previous_mode = self.mouse_look_mode;
+                egui::ComboBox::from_label("Mouse look")
+                    .selected_text(format!("{:?}", self.mouse_look_mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.mouse_look_mode, MouseLookMode::Hold, "Hold");
+                        ui.selectable_value(&mut self.mouse_look_mode, MouseLookMode::Toggle, "Toggle");
+                    });
+                if self.mouse_look_mode != previous_mode && self.mouse_look_toggled {
+                    self.mouse_look_toggled = false;
+                    self.cursor_grab_toggle_requested = self.cursor_grabbed;
+                }
                 ui.add(egui::Slider::new(&mut self.shininess, 2..=256).text("Specular shininess"));
-                ui.add(
-                    egui::Slider::new(&mut self.ambient_strength, 0.0..=1.0)
-                        .text("Ambient strength"),
-                );
+                // Shows the scene's ambient light, which is only written back when edited here
+                self.ambient_strength = scene.ambient_light.intensity;
+                self.ambient_color = scene.ambient_light.color.to_array();
+                let strength_changed = ui
+                    .add(
+                        egui::Slider::new(&mut self.ambient_strength, 0.0..=1.0)
+                            .text("Ambient intensity"),
+                    )
+                    .changed();
+                let color_changed = ui
+                    .horizontal(|ui| {
+                        ui.label("Ambient color:");
+                        ui.color_edit_button_rgb(&mut self.ambient_color).changed()
+                    })
+                    .inner;
+                if strength_changed {
+                    scene.ambient_light.intensity = self.ambient_strength.clamp(0.0, 1.0);
+                }
+                if color_changed {
+                    scene.ambient_light.color = glam::Vec3::from_array(self.ambient_color);
+                }
                 ui.add(
                     egui::Slider::new(&mut self.specular_strength, 0.0..=1.0)
                         .text("Specular strength"),
@@ -49,13 +340,491 @@ impl Ui {
                 ui.horizontal(|ui| {
                     ui.label("Light color:");
                     ui.color_edit_button_rgb(self.light_color.as_mut().try_into().unwrap());
+                    if ui.button("Set all").clicked() {
+                        for light in &scene.lights {
+                            light.borrow_mut().color = self.light_color;
+                        }
+                    }
+                });
+                ui.checkbox(&mut self.gradient_background, "Gradient background");
+                if self.gradient_background {
+                    ui.horizontal(|ui| {
+                        ui.label("Top:");
+                        ui.color_edit_button_rgb(&mut self.background_top);
+                        ui.label("Bottom:");
+                        ui.color_edit_button_rgb(&mut self.background_bottom);
+                    });
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.label("Clear color:");
+                        ui.color_edit_button_rgb(self.clear_color.as_mut().try_into().unwrap())
+                    });
+                }
+                ui.checkbox(&mut self.hdr, "HDR");
+                if self.hdr {
+                    ui.add(egui::Slider::new(&mut self.exposure, 0.1..=5.0).text("Exposure"));
+                    egui::ComboBox::from_label("Tone mapping")
+                        .selected_text(format!("{:?}", self.tone_mapping))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.tone_mapping, ToneMapping::Reinhard, "Reinhard");
+                            ui.selectable_value(&mut self.tone_mapping, ToneMapping::Aces, "Aces");
+                        });
+                    ui.checkbox(&mut self.bloom, "Bloom");
+                    if self.bloom {
+                        ui.add(egui::Slider::new(&mut self.bloom_threshold, 0.0..=5.0).text("Bloom threshold"));
+                        ui.add(egui::Slider::new(&mut self.bloom_intensity, 0.0..=2.0).text("Bloom intensity"));
+                    }
+                }
+                ui.checkbox(&mut self.depth_test, "Depth test");
+                ui.checkbox(&mut self.depth_prepass, "Depth prepass");
+                if self.depth_test {
+                    egui::ComboBox::from_label("Depth function")
+                        .selected_text(format!("{:?}", self.depth_func))
+                        .show_ui(ui, |ui| {
+                            for func in [
+                                DepthFunc::Less,
+                                DepthFunc::LessEqual,
+                                DepthFunc::Equal,
+                                DepthFunc::NotEqual,
+                                DepthFunc::Greater,
+                                DepthFunc::GreaterEqual,
+                                DepthFunc::Always,
+                                DepthFunc::Never,
+                            ] {
+                                ui.selectable_value(&mut self.depth_func, func, format!("{:?}", func));
+                            }
+                        });
+                }
+                ui.checkbox(&mut self.face_culling, "Cull back faces");
+                egui::ComboBox::from_label("Front face")
+                    .selected_text(format!("{:?}", self.front_face))
+                    .show_ui(ui, |ui| {
+                        for front_face in [FrontFace::Ccw, FrontFace::Cw] {
+                            ui.selectable_value(&mut self.front_face, front_face, format!("{:?}", front_face));
+                        }
+                    });
+                ui.checkbox(&mut self.polygon_offset, "Polygon offset");
+                if self.polygon_offset {
+                    ui.add(egui::Slider::new(&mut self.polygon_offset_factor, -4.0..=4.0).text("Offset factor"));
+                    ui.add(egui::Slider::new(&mut self.polygon_offset_units, -4.0..=4.0).text("Offset units"));
+                }
+                egui::ComboBox::from_label("Debug view")
+                    .selected_text(format!("{:?}", self.debug_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            DebugMode::None,
+                            DebugMode::Normals,
+                            DebugMode::Depth,
+                            DebugMode::Uvs,
+                            DebugMode::Overdraw,
+                        ] {
+                            ui.selectable_value(&mut self.debug_mode, mode, format!("{:?}", mode));
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("Wireframe (L):");
+                    ui.color_edit_button_rgb(&mut self.wireframe_color);
+                    ui.add(egui::Slider::new(&mut self.wireframe_width, 1.0..=10.0).text("Width"));
                 });
+                ui.checkbox(&mut self.split_view, "Split view");
                 ui.horizontal(|ui| {
-                    ui.label("Clear color:");
-                    ui.color_edit_button_rgb(self.clear_color.as_mut().try_into().unwrap())
+                    let mut outline = self.selected_object.is_some();
+                    let mut index = self.selected_object.unwrap_or(0);
+                    ui.checkbox(&mut outline, "Outline object");
+                    ui.add(egui::DragValue::new(&mut index).range(0..=self.object_count.saturating_sub(1)));
+                    self.selected_object = outline.then_some(index);
+                });
+                ui.checkbox(&mut self.picking, "Click to select (picking)");
+                if self.selected_object.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.label("Outline color:");
+                        ui.color_edit_button_rgb(&mut self.outline_color);
+                    });
+                    ui.add(egui::Slider::new(&mut self.outline_width, 0.01..=0.5).text("Outline width"));
+                }
+                ui.checkbox(&mut self.show_grid, "Show grid");
+                if self.show_grid {
+                    ui.add(egui::Slider::new(&mut self.grid_spacing, 0.1..=5.0).text("Grid spacing"));
+                    ui.add(egui::Slider::new(&mut self.grid_extent, 1.0..=100.0).text("Grid extent"));
+                }
+                ui.checkbox(&mut self.smooth_lines, "Smooth lines (grid and wireframe)");
+            });
+
+        self.light_editor(ctx, scene);
+        self.object_inspector(ctx, scene);
+        self.bookmarks(ctx, scene);
+        self.key_bindings_editor(ctx);
+        self.hardware_info(ctx);
+    }
+
+    // Material properties of the selected object, overrides are edited in place
+    fn object_inspector(&mut self, ctx: &Context, scene: &mut Scene) {
+        let Some(selected) = self.selected_object.and_then(|i| scene.objects.get(i)).cloned() else {
+            return;
+        };
+        let mut object = selected.borrow_mut();
+        let material = object.material();
+        let material = material.borrow();
+        let base = material.properties();
+        let mut names: Vec<String> = base
+            .names()
+            .chain(object.material_overrides.names())
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let mut duplicate = false;
+        egui::Window::new("Inspector").show(ctx, |ui| {
+            ui.label(format!("Object {}, material '{}'", self.selected_object.unwrap(), material.name()));
+            ui.checkbox(&mut object.visible, "Visible");
+            egui::Grid::new("material_properties").show(ui, |ui| {
+                for name in &names {
+                    ui.label(name);
+                    if let Some(value) = object.material_overrides.get_mut(name) {
+                        property_editor(ui, value);
+                        if ui.button("Reset").clicked() {
+                            object.material_overrides.delete(name);
+                        }
+                    } else if let Some(value) = base.get(name) {
+                        ui.add_enabled_ui(false, |ui| property_editor(ui, &mut value.clone()));
+                        if ui.button("Override").clicked() {
+                            object.material_overrides.set(name, value.clone());
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            if ui
+                .add_enabled(!object.material_overrides.is_empty(), egui::Button::new("Reset overrides"))
+                .clicked()
+            {
+                object.material_overrides.clear();
+            }
+            duplicate = ui.button("Duplicate").clicked();
+        });
+
+        // The copy becomes the selected object
+        drop(material);
+        drop(object);
+        if duplicate {
+            scene.duplicate_object(&selected);
+            self.selected_object = Some(scene.objects.len() - 1);
+        }
+    }
+
+    // Compact frame statistics in a corner of the window
+    fn stats_overlay(&self, ctx: &Context) {
+        let align = self.stats_overlay_corner.as_align();
+        let margin = 8.0;
+        let offset = egui::vec2(
+            if align.x() == egui::Align::Min { margin } else { -margin },
+            if align.y() == egui::Align::Min { margin } else { -margin },
+        );
+        egui::Area::new(egui::Id::new("stats_overlay"))
+            .anchor(align, offset)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("{} FPS, {:.2} ms", self.fps, self.frame_time_ms));
+                    ui.label(format!("{} draw calls", self.render_stats.draw_calls));
+                    ui.label(format!("{} triangles", self.render_stats.triangles));
+                });
+            });
+    }
+
+    fn hardware_info(&self, ctx: &Context) {
+        let Some(caps) = &self.capabilities else {
+            return;
+        };
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        egui::Window::new("About/Hardware")
+            .default_open(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("hardware_info").show(ui, |ui| {
+                    ui.label("Version");
+                    ui.label(&caps.version);
+                    ui.end_row();
+                    ui.label("Renderer");
+                    ui.label(&caps.renderer);
+                    ui.end_row();
+                    ui.label("Vendor");
+                    ui.label(&caps.vendor);
+                    ui.end_row();
+                    ui.label("Texture units");
+                    ui.label(caps.max_texture_units.to_string());
+                    ui.end_row();
+                    ui.label("Max UBO size");
+                    ui.label(format!("{} KiB", caps.max_uniform_block_size / 1024));
+                    ui.end_row();
+                    ui.label("Max anisotropy");
+                    ui.label(caps.max_anisotropy.map_or("unsupported".to_string(), |a| format!("{}x", a)));
+                    ui.end_row();
+                    ui.label("Line widths");
+                    ui.label(format!("{} - {}", caps.line_width_range[0], caps.line_width_range[1]));
+                    ui.end_row();
+                    ui.label("Compute shaders");
+                    ui.label(yes_no(caps.compute_shaders));
+                    ui.end_row();
+                    ui.label("Storage buffers");
+                    ui.label(yes_no(caps.storage_buffers));
+                    ui.end_row();
                 });
             });
     }
+
+    fn key_bindings_editor(&mut self, ctx: &Context) {
+        egui::Window::new("Key bindings")
+            .default_open(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("key_bindings").show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(format!("{:?}", action));
+                        let label = if self.rebinding == Some(action) {
+                            "Press a key...".to_string()
+                        } else {
+                            format!("{:?}", self.key_bindings.key(action))
+                        };
+                        if ui.button(label).clicked() {
+                            self.rebinding = Some(action);
+                            self.binding_error = None;
+                        }
+                        ui.end_row();
+                    }
+                });
+                if self.rebinding.is_some() {
+                    ui.label("Escape cancels");
+                }
+                if let Some(error) = &self.binding_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+    }
+
+    pub fn polygon_offset(&self) -> Option<PolygonOffset> {
+        self.polygon_offset.then_some(PolygonOffset {
+            factor: self.polygon_offset_factor,
+            units: self.polygon_offset_units,
+        })
+    }
+
+    /// Whether mouse movement should turn the camera
+    pub fn is_mouse_looking(&self, input: &InputManager) -> bool {
+        match self.mouse_look_mode {
+            MouseLookMode::Hold => input.is_mouse_button_pressed(MouseButton::Right),
+            MouseLookMode::Toggle => self.mouse_look_toggled,
+        }
+    }
+
+    /// Resets the state referring to objects and lights of the previous scene
+    pub fn scene_changed(&mut self, index: usize) {
+        self.active_scene = index;
+        self.selected_object = None;
+        self.light_temperatures.clear();
+        self.light_ranges.clear();
+    }
+
+    pub fn save_bookmark(&mut self, slot: usize, camera: &Camera) {
+        self.camera_bookmarks[slot] = Some(camera.pose());
+    }
+
+    /// Moves `camera` to the pose saved in `slot`, does nothing if the slot is empty
+    pub fn recall_bookmark(&self, slot: usize, camera: &mut Camera) {
+        let Some(pose) = self.camera_bookmarks[slot] else {
+            return;
+        };
+        if self.smooth_bookmarks {
+            camera.animate_to(pose, BOOKMARK_TRANSITION);
+        } else {
+            camera.set_pose(pose);
+        }
+    }
+
+    fn bookmarks(&mut self, ctx: &Context, scene: &mut Scene) {
+        egui::Window::new("Camera bookmarks")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.label("Ctrl + 1-9 saves, 1-9 recalls");
+                ui.checkbox(&mut self.smooth_bookmarks, "Smooth transition");
+                for slot in 0..BOOKMARK_SLOTS {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", slot + 1));
+                        if ui.button("Save").clicked() {
+                            self.save_bookmark(slot, scene.camera());
+                        }
+                        let saved = self.camera_bookmarks[slot];
+                        if ui
+                            .add_enabled(saved.is_some(), egui::Button::new("Recall"))
+                            .clicked()
+                        {
+                            self.recall_bookmark(slot, scene.camera_mut());
+                        }
+                        if let Some(pose) = saved {
+                            ui.label(format!(
+                                "({:.1}, {:.1}, {:.1})",
+                                pose.position.x, pose.position.y, pose.position.z
+                            ));
+                        }
+                    });
+                }
+            });
+    }
+
+    fn light_editor(&mut self, ctx: &Context, scene: &mut Scene) {
+        self.light_temperatures.resize(scene.lights.len(), 6500.0);
+        self.light_ranges.resize(scene.lights.len(), 50.0);
+
+        egui::Window::new("Lights")
+            .default_open(false)
+            .show(ctx, |ui| {
+                for (i, light) in scene.lights.iter().enumerate() {
+                    let mut light = light.borrow_mut();
+                    egui::CollapsingHeader::new(format!("{} {}", light_kind(&light), i))
+                        .id_salt(i)
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Color:");
+                                ui.color_edit_button_rgb(&mut light.color);
+                            });
+                            ui.horizontal(|ui| {
+                                // Starts from the current highlights so nothing jumps when enabled
+                                let mut separate = light.specular_color.is_some();
+                                ui.checkbox(&mut separate, "Specular color:");
+                                if separate {
+                                    let mut specular = light.specular();
+                                    ui.color_edit_button_rgb(&mut specular);
+                                    light.specular_color = Some(specular);
+                                } else {
+                                    light.specular_color = None;
+                                }
+                            });
+                            ui.add(
+                                egui::Slider::new(&mut light.intensity, 0.0..=5.0)
+                                    .text("Intensity"),
+                            );
+                            let temperature = &mut self.light_temperatures[i];
+                            let response = ui.add(
+                                egui::Slider::new(temperature, 1000.0..=12000.0)
+                                    .text("Temperature (K)"),
+                            );
+                            if response.changed() {
+                                light.set_color_temperature(*temperature);
+                            }
+
+                            if !light.is_directional_light() {
+                                let range = &mut self.light_ranges[i];
+                                let response = ui.add(
+                                    egui::Slider::new(range, 7.0..=3250.0)
+                                        .logarithmic(true)
+                                        .text("Range (m)"),
+                                );
+                                if response.changed() {
+                                    if let Some(point) = light.as_point_light_mut() {
+                                        point.set_range(*range);
+                                    } else if let Some(spot) = light.as_spot_light_mut() {
+                                        spot.set_range(*range);
+                                    } else if let Some(area) = light.as_area_light_mut() {
+                                        area.set_range(*range);
+                                    }
+                                }
+                            }
+
+                            if let Some(falloff) = light.falloff_mut() {
+                                egui::ComboBox::new(("falloff", i), "Falloff")
+                                    .selected_text(format!("{:?}", falloff))
+                                    .show_ui(ui, |ui| {
+                                        for mode in [Falloff::Coefficients, Falloff::InverseSquare] {
+                                            ui.selectable_value(falloff, mode, format!("{:?}", mode));
+                                        }
+                                    });
+                            }
+
+                            if let Some(spot) = light.as_spot_light_mut() {
+                                ui.checkbox(&mut spot.attached_to_camera, "Follow camera");
+                                // Edited in degrees, the cone edge fades from the inner to the outer angle
+                                let mut inner = spot.inner_cutoff_rad.to_degrees();
+                                let mut outer = spot.outer_cutoff_rad.to_degrees();
+                                let inner_changed = ui
+                                    .add(egui::Slider::new(&mut inner, 0.1..=90.0).suffix("°").text("Inner cone"))
+                                    .changed();
+                                let outer_changed = ui
+                                    .add(egui::Slider::new(&mut outer, 0.1..=90.0).suffix("°").text("Outer cone"))
+                                    .changed();
+                                // Keep inner <= outer by moving whichever angle wasn't dragged
+                                if inner_changed {
+                                    outer = outer.max(inner);
+                                } else if outer_changed {
+                                    inner = inner.min(outer);
+                                }
+                                if inner_changed || outer_changed {
+                                    spot.inner_cutoff_rad = inner.clamp(0.1, 90.0).to_radians();
+                                    spot.outer_cutoff_rad = outer.clamp(0.1, 90.0).to_radians();
+                                }
+                            }
+                        });
+                }
+            });
+    }
+}
+
+// Widget for a single material property, matrices and textures are only displayed
+fn property_editor(ui: &mut egui::Ui, value: &mut MaterialProperty) {
+    match value {
+        MaterialProperty::Boolean(b) => {
+            ui.checkbox(b, "");
+        }
+        MaterialProperty::Integer(i) => {
+            ui.add(egui::DragValue::new(i));
+        }
+        MaterialProperty::UInteger(u) => {
+            ui.add(egui::DragValue::new(u));
+        }
+        MaterialProperty::Float(f) => {
+            ui.add(egui::DragValue::new(f).speed(0.01));
+        }
+        MaterialProperty::Vec2(v) => {
+            ui.horizontal(|ui| {
+                for c in v {
+                    ui.add(egui::DragValue::new(c).speed(0.01));
+                }
+            });
+        }
+        MaterialProperty::Vec3(v) => {
+            ui.horizontal(|ui| {
+                for c in v {
+                    ui.add(egui::DragValue::new(c).speed(0.01));
+                }
+            });
+        }
+        MaterialProperty::Color(r, g, b) => {
+            // The picker works in linear space
+            let mut color = [*r, *g, *b].map(srgb_to_linear);
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                [*r, *g, *b] = color.map(linear_to_srgb);
+            }
+        }
+        MaterialProperty::Mat3(_) => {
+            ui.label("3x3 matrix");
+        }
+        MaterialProperty::Mat4(_) => {
+            ui.label("4x4 matrix");
+        }
+        MaterialProperty::Texture(texture) => {
+            ui.label(format!("Texture {}", texture.id()));
+        }
+    }
+}
+
+fn light_kind(light: &Light) -> &'static str {
+    if light.is_spot_light() {
+        "Spot light"
+    } else if light.is_directional_light() {
+        "Directional light"
+    } else if light.is_area_light() {
+        "Area light"
+    } else {
+        "Point light"
+    }
 }
 
 impl Default for Ui {
@@ -63,3 +832,26 @@ impl Default for Ui {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ambient_light_is_shown_from_the_scene_without_overwriting_it() {
+        let mut scene = Scene::new();
+        scene.ambient_light.intensity = 0.4;
+        scene.ambient_light.color = glam::vec3(1.0, 0.5, 0.25);
+        let mut ui = Ui::default();
+
+        let ctx = Context::default();
+        for _ in 0..2 {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| ui.run(ctx, &mut scene));
+        }
+        assert_eq!(scene.ambient_light.intensity, 0.4);
+        assert_eq!(scene.ambient_light.color, glam::vec3(1.0, 0.5, 0.25));
+        assert_eq!(ui.ambient_strength, 0.4);
+        // The color picker may round the shown copy a little, the scene keeps its value
+        assert!(glam::Vec3::from_array(ui.ambient_color).abs_diff_eq(glam::vec3(1.0, 0.5, 0.25), 1e-5));
+    }
+}